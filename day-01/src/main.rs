@@ -1,76 +1,740 @@
 #![forbid(unsafe_code)]
 
 use std::env;
-use std::fs::read_to_string;
+use std::fmt::{self, Display};
+use std::fs::{self, File, read_to_string};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::ops::AddAssign;
 use std::path::Path;
+use std::str::FromStr;
+
+mod odometer;
+#[cfg(test)]
+mod rng;
+mod solver;
+mod svg;
+
+use odometer::{Odometer, count_odometer_hits, parse_odometer_input};
+use solver::{Edit, find_edits};
+use svg::render_svg;
 
 fn main() -> Result<(), String> {
-    let filename = env::args()
-        .nth(1)
-        .ok_or_else(|| "No file name given.".to_owned())?;
-    let content = read_to_string(Path::new(&filename)).map_err(|e| e.to_string())?;
-    let instructions = parse_input(&content)?;
+    let options = parse_args(env::args().skip(1))?;
+
+    if options.stream {
+        let mut out = io::stdout().lock();
+        let (landed, passed) = if options.filename == "-" {
+            stream_passwords(&options.dial, io::stdin().lock(), &mut out)?
+        } else {
+            let file = File::open(Path::new(&options.filename)).map_err(|e| e.to_string())?;
+            stream_passwords(&options.dial, BufReader::new(file), &mut out)?
+        };
+        println!("The password is '{landed}'");
+        println!("No wait, it is actually '{passed}'");
+        return Ok(());
+    }
+
+    let content = read_to_string(Path::new(&options.filename)).map_err(|e| e.to_string())?;
+
+    if let Some(n_dials) = options.dials {
+        let instructions = parse_odometer_input(&content, n_dials)?;
+        let mut odometer = Odometer::new(options.dial, n_dials);
+        let hits = count_odometer_hits(&mut odometer, &instructions);
+        for (i, dial_hits) in hits.per_dial.iter().enumerate() {
+            println!("Dial {i} pointed at the mark {dial_hits} times");
+        }
+        println!(
+            "All dials pointed at the mark after {} instructions",
+            hits.combined
+        );
+        return Ok(());
+    }
+
+    let (origins, instructions): (Vec<Origin>, Vec<Instruction>) =
+        parse_input_with_origins(&content)?.into_iter().unzip();
 
-    let password = rot_and_count_0(&instructions);
+    if let Some(format) = options.trace {
+        // the trace goes to stderr so it does not get mixed up with the answers
+        let mut out = BufWriter::new(io::stderr().lock());
+        write_trace(&mut out, format, &options.dial, &instructions).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(svg_file) = &options.svg {
+        let svg = render_svg(&options.dial, &instructions, options.counting);
+        fs::write(Path::new(svg_file), svg).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(target) = options.target {
+        return print_edits(
+            &options.dial,
+            &instructions,
+            &origins,
+            options.counting,
+            target,
+        );
+    }
+
+    let password = rot_and_count_0(&options.dial, &instructions);
     println!("The password is '{password}'");
 
-    let password = clilb(&instructions);
+    let password = clilb(&options.dial, &instructions);
     println!("No wait, it is actually '{password}'");
 
     Ok(())
 }
 
-fn rot_and_count_0(instructions: &[i32]) -> u32 {
-    let mut count: u32 = 0;
-    let mut pos: i32 = 50;
+fn print_edits(
+    dial: &Dial,
+    instructions: &[Instruction],
+    origins: &[Origin],
+    counting: Counting,
+    target: usize,
+) -> Result<(), String> {
+    let Some(changes) = find_edits(dial, instructions, counting, target)? else {
+        println!("There is no way to get the password '{target}' by changing instructions");
+        return Ok(());
+    };
+    println!(
+        "{} changes are needed to get the password '{target}'",
+        changes.len()
+    );
+    for change in changes {
+        let inst = instructions[change.index];
+        let origin = origins[change.index];
+        match (change.edit, change.edit.apply(inst)) {
+            (Edit::Delete, _) => println!("{origin}: delete {inst}"),
+            (Edit::Flip, Some(edited)) => println!("{origin}: flip {inst} to {edited}"),
+            (Edit::Magnitude(_), Some(edited)) => println!("{origin}: change {inst} to {edited}"),
+            (edit, _) => return Err(format!("unexpected edit {edit:?} for {inst}")),
+        }
+    }
+    Ok(())
+}
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Options {
+    filename: String,
+    dial: Dial,
+    trace: Option<TraceFormat>,
+    dials: Option<usize>,
+    target: Option<usize>,
+    counting: Counting,
+    stream: bool,
+    svg: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut filename: Option<String> = None;
+    let mut size: Option<i64> = None;
+    let mut start: Option<i64> = None;
+    let mut mark: Option<i64> = None;
+    let mut trace: Option<TraceFormat> = None;
+    let mut dials: Option<usize> = None;
+    let mut target: Option<usize> = None;
+    let mut counting: Option<Counting> = None;
+    let mut stream = false;
+    let mut svg: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => size = Some(parse_option_value(&arg, args.next())?),
+            "--start" => start = Some(parse_option_value(&arg, args.next())?),
+            "--mark" => mark = Some(parse_option_value(&arg, args.next())?),
+            "--trace" => trace = Some(parse_option_value(&arg, args.next())?),
+            "--dials" => dials = Some(parse_option_value(&arg, args.next())?),
+            "--target" => target = Some(parse_option_value(&arg, args.next())?),
+            "--counting" => counting = Some(parse_option_value(&arg, args.next())?),
+            "--stream" => stream = true,
+            "--svg" => svg = Some(parse_option_value(&arg, args.next())?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            _ => {
+                if filename.is_some() {
+                    return Err(format!("unexpected extra argument '{arg}'"));
+                }
+                filename = Some(arg);
+            }
+        }
+    }
+
+    if let Some(dials) = dials
+        && !(1..=MAX_DIALS).contains(&dials)
+    {
+        return Err(format!(
+            "the number of dials must be between 1 and {MAX_DIALS}, but is {dials}"
+        ));
+    }
+    if dials.is_some()
+        && (target.is_some() || trace.is_some() || svg.is_some() || counting.is_some())
+    {
+        return Err(
+            "'--dials' cannot be combined with '--target', '--trace', '--svg' or '--counting'"
+                .to_owned(),
+        );
+    }
+    if stream && (dials.is_some() || target.is_some() || trace.is_some() || svg.is_some()) {
+        return Err("'--stream' only works for counting on a single dial".to_owned());
+    }
+    let size = size.unwrap_or(DEFAULT_DIAL_SIZE);
+    let dial = Dial::new(size, start.unwrap_or(size / 2), mark.unwrap_or(0))?;
+    Ok(Options {
+        // when streaming, we can read from stdin
+        filename: filename
+            .or_else(|| stream.then(|| "-".to_owned()))
+            .ok_or_else(|| "No file name given.".to_owned())?,
+        dial,
+        trace,
+        dials,
+        target,
+        counting: counting.unwrap_or(Counting::Passing),
+        stream,
+        svg,
+    })
+}
+
+fn parse_option_value<T>(option: &str, value: Option<String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| format!("option '{option}' needs a value"))?;
+    value
+        .parse()
+        .map_err(|e| format!("invalid value '{value}' for option '{option}': {e}"))
+}
+
+const DEFAULT_DIAL_SIZE: i64 = 100;
+
+// every dial of an odometer needs its own position and hit count
+const MAX_DIALS: usize = 1 << 20;
+
+// positions on the dial go from 0 to size - 1, the dial starts at `start`,
+// and we count how often it points at `mark`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Dial {
+    size: i64,
+    start: i64,
+    mark: i64,
+}
+
+impl Default for Dial {
+    fn default() -> Self {
+        Dial {
+            size: DEFAULT_DIAL_SIZE,
+            start: DEFAULT_DIAL_SIZE / 2,
+            mark: 0,
+        }
+    }
+}
+
+impl Dial {
+    fn new(size: i64, start: i64, mark: i64) -> Result<Dial, String> {
+        if size < 1 {
+            return Err(format!("dial size must be positive, but is {size}"));
+        }
+        if !(0..size).contains(&start) {
+            return Err(format!(
+                "start position {start} is not on a dial of size {size}"
+            ));
+        }
+        if !(0..size).contains(&mark) {
+            return Err(format!("mark {mark} is not on a dial of size {size}"));
+        }
+        Ok(Dial { size, start, mark })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Instruction {
+    Rotate(i128),
+    // positions outside of the dial wrap around, just like rotations do
+    Set(i64),
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Rotate(clicks) if *clicks < 0 => write!(f, "L{}", clicks.unsigned_abs()),
+            Instruction::Rotate(clicks) => write!(f, "R{clicks}"),
+            Instruction::Set(pos) => write!(f, "={pos}"),
+        }
+    }
+}
+
+// what counts as the dial pointing at the mark
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Counting {
+    // only at the end of an instruction, like `rot_and_count_0`
+    Landing,
+    // after any click, like `clilb`
+    Passing,
+}
+
+impl FromStr for Counting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "landing" => Ok(Counting::Landing),
+            "passing" => Ok(Counting::Passing),
+            _ => Err("expected 'landing' or 'passing'".to_owned()),
+        }
+    }
+}
+
+fn rot_and_count_0(dial: &Dial, instructions: &[Instruction]) -> usize {
+    steps(dial, instructions)
+        .filter(|step| step.to == dial.mark)
+        .count()
+}
+
+fn clilb(dial: &Dial, instructions: &[Instruction]) -> HitCount {
+    let mut count = HitCount::default();
+    for step in steps(dial, instructions) {
+        count += step.hits;
+    }
+    count
+}
+
+// Turns the dial one click at a time. This is way too slow for real inputs, but it is
+// obviously correct, so we can check `clilb` against it.
+#[cfg(test)]
+fn clilb_by_clicks(dial: &Dial, instructions: &[Instruction]) -> HitCount {
+    let mut count = HitCount::default();
+    let mut pos = dial.start;
     for inst in instructions {
-        pos = (pos + inst).rem_euclid(100);
-        if pos == 0 {
-            count += 1;
+        match *inst {
+            Instruction::Rotate(clicks) => {
+                for _ in 0..clicks.unsigned_abs() {
+                    pos = (pos + clicks.signum() as i64).rem_euclid(dial.size);
+                    if pos == dial.mark {
+                        count += 1;
+                    }
+                }
+            }
+            Instruction::Set(to) => {
+                let to = to.rem_euclid(dial.size);
+                if to != pos && to == dial.mark {
+                    count += 1;
+                }
+                pos = to;
+            }
         }
     }
     count
 }
 
-fn clilb(instructions: &[i32]) -> u32 {
-    let mut count: u32 = 0;
-    let mut pos: i32 = 50;
+fn steps(dial: &Dial, instructions: &[Instruction]) -> impl Iterator<Item = Step> {
+    let mut pos = dial.start;
+    instructions.iter().map(move |inst| {
+        let step = dial.apply(pos, *inst);
+        pos = step.to;
+        step
+    })
+}
 
-    for inst in instructions.iter().copied() {
-        count += (inst / 100).unsigned_abs();
-        let pos_orig = pos;
-        pos = (pos + inst).rem_euclid(100);
-        if pos_orig != 0
-            && ((inst > 0 && pos_orig > pos) || (inst < 0 && pos_orig < pos || pos == 0))
-        {
-            println!("from {pos_orig} to {pos}");
-            count += 1;
+// Reads the instructions line by line and writes both passwords after each instruction,
+// so we can handle inputs of any length. Returns the final passwords.
+fn stream_passwords(
+    dial: &Dial,
+    mut input: impl BufRead,
+    out: &mut impl Write,
+) -> Result<(HitCount, HitCount), String> {
+    let mut pos = dial.start;
+    let mut landed = HitCount::default();
+    let mut passed = HitCount::default();
+    let mut line = String::new();
+    let mut line_no: usize = 0;
+    loop {
+        line.clear();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            break;
         }
+        line_no += 1;
+        let Some((inst, times)) = parse_line(&line).map_err(|e| format!("line {line_no}: {e}"))?
+        else {
+            continue;
+        };
+        let repetition = dial.repeat(pos, inst, times);
+        pos = repetition.to;
+        landed += repetition.landed;
+        passed += repetition.hits;
+        writeln!(out, "{line_no}: {landed} {passed}").map_err(|e| e.to_string())?;
     }
-    count
+    Ok((landed, passed))
 }
 
-fn parse_input(input: &str) -> Result<Vec<i32>, String> {
-    input.lines().map(parse_line).collect()
+// what happens during a single rotation of the dial
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Step {
+    from: i64,
+    to: i64,
+    // number of full turns
+    wraps: u128,
+    // how often the dial points at the mark during the rotation, including the end position
+    hits: u128,
 }
 
-fn parse_line(line: &str) -> Result<i32, String> {
-    if let Some(n) = line.strip_prefix('R') {
+impl Dial {
+    fn apply(&self, from: i64, inst: Instruction) -> Step {
+        match inst {
+            Instruction::Rotate(clicks) => self.rotate(from, clicks),
+            Instruction::Set(pos) => self.set(from, pos),
+        }
+    }
+
+    // setting the dial jumps to the position without passing anything in between,
+    // so it only hits the mark if it lands on it
+    fn set(&self, from: i64, pos: i64) -> Step {
+        let to = pos.rem_euclid(self.size);
+        Step {
+            from,
+            to,
+            wraps: 0,
+            hits: (from != self.mark && to == self.mark) as u128,
+        }
+    }
+
+    fn rotate(&self, from: i64, inst: i128) -> Step {
+        let size = self.size as i128;
+        // cannot overflow, since both summands are smaller than 2^63
+        let to = (from as i128 + inst.rem_euclid(size)).rem_euclid(size) as i64;
+
+        let clicks = inst.unsigned_abs();
+        let wraps = clicks / size as u128;
+        let rest = clicks % size as u128;
+        // distance from the mark in the direction of the rotation
+        let rel_from = if inst >= 0 {
+            (from - self.mark).rem_euclid(self.size)
+        } else {
+            (self.mark - from).rem_euclid(self.size)
+        };
+        // after the full turns, the remaining clicks hit the mark iff they reach or pass it
+        let hits = wraps + (rel_from != 0 && rest + rel_from as u128 >= size as u128) as u128;
+        Step {
+            from,
+            to,
+            wraps,
+            hits,
+        }
+    }
+
+    // Applies the instruction `times` times in a row, without going through the repetitions
+    // one by one.
+    fn repeat(&self, from: i64, inst: Instruction, times: usize) -> Repetition {
+        if times == 0 {
+            return Repetition {
+                to: from,
+                landed: 0,
+                hits: HitCount::default(),
+            };
+        }
+        let clicks = match inst {
+            Instruction::Rotate(clicks) => clicks,
+            Instruction::Set(_) => {
+                // after the first time, the dial already is where it is set to
+                let step = self.apply(from, inst);
+                return Repetition {
+                    to: step.to,
+                    landed: if step.to == self.mark {
+                        times as u128
+                    } else {
+                        0
+                    },
+                    hits: step.hits.into(),
+                };
+            }
+        };
+        let size = self.size as u128;
+        let times = times as u128;
+        // For passing the mark, the repetitions are one long rotation. Its full turns may not
+        // fit into 128 bits, but they don't move the dial, so we only rotate by the rest.
+        let turns = clicks.unsigned_abs() / size;
+        // cannot overflow, since the rest is below 2^63 and `times` below 2^64
+        let rest = clicks.unsigned_abs() % size * times;
+        let step = self.rotate(from, clicks.signum() * rest as i128);
+        let mut hits = HitCount::product(turns, times);
+        hits += step.hits;
+        Repetition {
+            to: step.to,
+            landed: self.landings(from, clicks, times),
+            hits,
+        }
+    }
+
+    // How often the dial lands on the mark when rotating by `clicks` `times` times. The
+    // positions are periodic, so we solve `j * clicks ≡ mark - from (mod size)` for the first
+    // repetition j that lands on the mark and count the periods after it.
+    fn landings(&self, from: i64, clicks: i128, times: u128) -> u128 {
+        let size = self.size as i128;
+        let clicks = clicks.rem_euclid(size);
+        let distance = (self.mark - from).rem_euclid(self.size) as i128;
+        let (gcd, inverse) = extended_gcd(clicks, size);
+        if distance % gcd != 0 {
+            return 0;
+        }
+        let period = size / gcd;
+        let first = match (distance / gcd * inverse).rem_euclid(period) {
+            0 => period,
+            first => first,
+        } as u128;
+        if times < first {
+            0
+        } else {
+            (times - first) / period as u128 + 1
+        }
+    }
+}
+
+// the greatest common divisor g of a and b, and some x with a * x ≡ g (mod b)
+fn extended_gcd(a: i128, b: i128) -> (i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_x, x) = (x, old_x - quotient * x);
+    }
+    (old_r, old_x)
+}
+
+// what happens when the same instruction is applied several times in a row
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Repetition {
+    to: i64,
+    // how often the dial ended up at the mark after one of the repetitions
+    landed: u128,
+    hits: HitCount,
+}
+
+// a single rotation can hit the mark almost 2^127 times, and a repeated one almost 2^191
+// times, so summing up hits in a u128 may overflow. The extra 128 bits are enough for any
+// number of instructions we can process.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+struct HitCount {
+    high: u128,
+    low: u128,
+}
+
+impl HitCount {
+    // `factor * times` for times below 2^64, which does not always fit into a u128
+    fn product(factor: u128, times: u128) -> HitCount {
+        let low = (factor as u64 as u128) * times;
+        let high = (factor >> 64) * times;
+        let mut product = HitCount {
+            high: high >> 64,
+            low,
+        };
+        product += high << 64;
+        product
+    }
+}
+
+impl From<u128> for HitCount {
+    fn from(low: u128) -> Self {
+        HitCount { high: 0, low }
+    }
+}
+
+impl AddAssign<u128> for HitCount {
+    fn add_assign(&mut self, rhs: u128) {
+        let (low, carry) = self.low.overflowing_add(rhs);
+        self.low = low;
+        self.high += carry as u128;
+    }
+}
+
+impl AddAssign<HitCount> for HitCount {
+    fn add_assign(&mut self, rhs: HitCount) {
+        *self += rhs.low;
+        self.high += rhs.high;
+    }
+}
+
+impl Display for HitCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.high == 0 {
+            return write!(f, "{}", self.low);
+        }
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        // long division by 10^19, most significant limb first
+        let mut limbs = [
+            (self.high >> 64) as u64,
+            self.high as u64,
+            (self.low >> 64) as u64,
+            self.low as u64,
+        ];
+        let mut chunks: Vec<u64> = Vec::with_capacity(8);
+        while limbs.iter().any(|limb| *limb != 0) {
+            let mut rem: u128 = 0;
+            for limb in limbs.iter_mut() {
+                let current = (rem << 64) | *limb as u128;
+                *limb = (current / CHUNK) as u64;
+                rem = current % CHUNK;
+            }
+            chunks.push(rem as u64);
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{first}")?;
+        }
+        for chunk in chunks {
+            write!(f, "{chunk:019}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TraceFormat {
+    JsonLines,
+    Csv,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(TraceFormat::JsonLines),
+            "csv" => Ok(TraceFormat::Csv),
+            _ => Err("expected 'jsonl' or 'csv'".to_owned()),
+        }
+    }
+}
+
+// one record per instruction, the count is the running count of `clilb`
+fn write_trace(
+    out: &mut impl Write,
+    format: TraceFormat,
+    dial: &Dial,
+    instructions: &[Instruction],
+) -> io::Result<()> {
+    if format == TraceFormat::Csv {
+        writeln!(out, "step,start,end,wraps,crossings,count")?;
+    }
+    let mut count = HitCount::default();
+    for (i, step) in steps(dial, instructions).enumerate() {
+        count += step.hits;
+        let Step {
+            from,
+            to,
+            wraps,
+            hits,
+        } = step;
+        match format {
+            TraceFormat::JsonLines => writeln!(
+                out,
+                r#"{{"step":{i},"start":{from},"end":{to},"wraps":{wraps},"crossings":{hits},"count":{count}}}"#
+            )?,
+            TraceFormat::Csv => writeln!(out, "{i},{from},{to},{wraps},{hits},{count}")?,
+        }
+    }
+    out.flush()
+}
+
+// where an instruction comes from: its line in the input and, for repeated rotations, which
+// of the repetitions it is (counted from 1)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Origin {
+    line: usize,
+    repetition: usize,
+    times: usize,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.times == 1 {
+            write!(f, "line {}", self.line)
+        } else {
+            write!(
+                f,
+                "line {}, repetition {} of {}",
+                self.line, self.repetition, self.times
+            )
+        }
+    }
+}
+
+// most tests don't care where the instructions come from
+#[cfg(test)]
+fn parse_input(input: &str) -> Result<Vec<Instruction>, String> {
+    Ok(parse_input_with_origins(input)?
+        .into_iter()
+        .map(|(_, inst)| inst)
+        .collect())
+}
+
+// Repetitions are expanded when reading the whole input, so we need a limit on how many
+// instructions that makes. Streaming does not expand them and has no such limit.
+const MAX_INSTRUCTIONS: usize = 1 << 22;
+
+fn parse_input_with_origins(input: &str) -> Result<Vec<(Origin, Instruction)>, String> {
+    let mut instructions: Vec<(Origin, Instruction)> = Vec::with_capacity(input.lines().count());
+    for (i, line) in input.lines().enumerate() {
+        if let Some((instruction, times)) =
+            parse_line(line).map_err(|e| format!("line {}: {e}", i + 1))?
+        {
+            if times > MAX_INSTRUCTIONS - instructions.len() {
+                return Err(format!(
+                    "line {}: more than {MAX_INSTRUCTIONS} instructions after repetitions, use '--stream' for longer inputs",
+                    i + 1
+                ));
+            }
+            instructions.extend((1..=times).map(|repetition| {
+                let origin = Origin {
+                    line: i + 1,
+                    repetition,
+                    times,
+                };
+                (origin, instruction)
+            }));
+        }
+    }
+    Ok(instructions)
+}
+
+// Returns the instruction and how often it shall be repeated, or `None` for lines without
+// instruction. Supported are rotations (`R10`, `L5`), repeated rotations (`R10x5`),
+// setting the dial to an absolute position (`=37`), blank lines and `#` comments.
+fn parse_line(line: &str) -> Result<Option<(Instruction, usize)>, String> {
+    let inst = line.split_once('#').map_or(line, |(inst, _)| inst).trim();
+    if inst.is_empty() {
+        return Ok(None);
+    }
+    if let Some(pos) = inst.strip_prefix('=') {
+        return pos
+            .parse()
+            .map(|pos| Some((Instruction::Set(pos), 1)))
+            .map_err(|e| format!("unable to parse position in '{inst}': {e}"));
+    }
+    let (clicks, times) = match inst.split_once('x') {
+        Some((clicks, times)) => (
+            clicks,
+            times
+                .parse::<usize>()
+                .map_err(|e| format!("unable to parse repetitions in '{inst}': {e}"))?,
+        ),
+        None => (inst, 1),
+    };
+    let rotation = if let Some(n) = clicks.strip_prefix('R') {
         n.parse()
-            .map_err(|e| format!("unable to parse line '{line}': {e})"))
-    } else if let Some(n) = line.strip_prefix('L') {
-        n.parse::<i32>()
+            .map_err(|e| format!("unable to parse rotation '{inst}': {e}"))?
+    } else if let Some(n) = clicks.strip_prefix('L') {
+        n.parse::<i128>()
             .map(|l| -l)
-            .map_err(|e| format!("unable to parse line '{line}': {e})"))
+            .map_err(|e| format!("unable to parse rotation '{inst}': {e}"))?
     } else {
-        Err(format!("line '{line}' does not start wth 'R' or 'L'"))
-    }
+        return Err(format!(
+            "instruction '{inst}' does not start with 'R', 'L' or '='"
+        ));
+    };
+    Ok(Some((Instruction::Rotate(rotation), times)))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rng::Rng;
 
     static EXAMPLE_INPUT: &str = r#"L68
 L30
@@ -84,27 +748,452 @@ R14
 L82
 "#;
 
+    fn rotations(clicks: &[i128]) -> Vec<Instruction> {
+        clicks.iter().copied().map(Instruction::Rotate).collect()
+    }
+
+    fn random_case(rng: &mut Rng) -> (Dial, Vec<Instruction>) {
+        let size = rng.below(20) as i64 + 1;
+        let dial = Dial::new(
+            size,
+            rng.below(size as u64) as i64,
+            rng.below(size as u64) as i64,
+        )
+        .expect("expected valid dial");
+        let len = rng.below(25) as usize;
+        let instructions = (0..len)
+            .map(|_| {
+                if rng.below(10) == 0 {
+                    Instruction::Set(rng.below(3 * size as u64) as i64 - size)
+                } else {
+                    let max_clicks = 4 * size as u64;
+                    Instruction::Rotate(rng.below(2 * max_clicks + 1) as i128 - max_clicks as i128)
+                }
+            })
+            .collect();
+        (dial, instructions)
+    }
+
+    // Makes a failing list of instructions as small as possible by dropping instructions and
+    // moving rotations towards 0, as long as it keeps failing.
+    fn shrink(
+        mut instructions: Vec<Instruction>,
+        fails: impl Fn(&[Instruction]) -> bool,
+    ) -> Vec<Instruction> {
+        let mut shrunk = true;
+        while shrunk {
+            shrunk = false;
+            let mut candidates: Vec<Vec<Instruction>> = Vec::new();
+            for i in 0..instructions.len() {
+                let mut candidate = instructions.clone();
+                candidate.remove(i);
+                candidates.push(candidate);
+                if let Instruction::Rotate(clicks) = instructions[i] {
+                    for smaller in [clicks / 2, clicks - clicks.signum()] {
+                        if smaller != clicks {
+                            let mut candidate = instructions.clone();
+                            candidate[i] = Instruction::Rotate(smaller);
+                            candidates.push(candidate);
+                        }
+                    }
+                }
+            }
+            if let Some(candidate) = candidates.into_iter().find(|c| fails(c)) {
+                instructions = candidate;
+                shrunk = true;
+            }
+        }
+        instructions
+    }
+
+    #[test]
+    fn clilb_agrees_with_click_by_click_simulation() {
+        let mut rng = Rng(0x2025_1201);
+        for _ in 0..5000 {
+            // given
+            let (dial, instructions) = random_case(&mut rng);
+
+            // when
+            let fails = |instructions: &[Instruction]| {
+                clilb(&dial, instructions) != clilb_by_clicks(&dial, instructions)
+            };
+
+            // then
+            if fails(&instructions) {
+                let instructions = shrink(instructions, fails);
+                panic!(
+                    "clilb and clilb_by_clicks disagree for {dial:?} and {instructions:?}: {} vs. {}",
+                    clilb(&dial, &instructions),
+                    clilb_by_clicks(&dial, &instructions)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shrink_finds_minimal_failing_case() {
+        // given
+        let instructions = rotations(&[3, -17, 40, 12, -2, 9]);
+
+        // when
+        let shrunk = shrink(instructions, |instructions| {
+            instructions
+                .iter()
+                .any(|inst| matches!(inst, Instruction::Rotate(clicks) if *clicks >= 10))
+        });
+
+        // then
+        assert_eq!(shrunk, rotations(&[10]));
+    }
+
     #[test]
     fn glilb_works_for_example() {
         // given
         let instructions = parse_input(EXAMPLE_INPUT).expect("epected valid input");
 
         // when
-        let password = clilb(&instructions);
+        let password = clilb(&Dial::default(), &instructions);
 
         // then
-        assert_eq!(password, 6);
+        assert_eq!(password, 6.into());
     }
 
     #[test]
     fn glilb_works_for_high_rotations() {
         // given
-        let instructions = &[1050, -1050];
+        let instructions = &rotations(&[1050, -1050]);
+
+        // when
+        let password = clilb(&Dial::default(), instructions);
+
+        // then
+        assert_eq!(password, 21.into());
+    }
+
+    #[test]
+    fn glilb_works_for_huge_rotations() {
+        // given
+        let instructions = &rotations(&[10i128.pow(30) + 49, -(10i128.pow(30))]);
+
+        // when
+        let password = clilb(&Dial::default(), instructions);
+
+        // then
+        assert_eq!(password, (2 * 10u128.pow(28)).into());
+    }
+
+    #[test]
+    fn glilb_count_does_not_overflow() {
+        // given
+        let instructions = &rotations(&[i128::MAX, i128::MIN, i128::MAX]);
+        let dial = Dial::new(1, 0, 0).expect("expected valid dial");
+
+        // when
+        let password = clilb(&dial, instructions);
+
+        // then
+        assert_eq!(
+            password.to_string(),
+            "510423550381407695195061911147652317182"
+        );
+    }
+
+    #[test]
+    fn rot_and_count_0_works_for_example() {
+        // given
+        let instructions = parse_input(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let password = rot_and_count_0(&Dial::default(), &instructions);
+
+        // then
+        assert_eq!(password, 3);
+    }
+
+    #[test]
+    fn counting_works_for_other_dial_geometries() {
+        // given
+        let dial = Dial::new(10, 3, 7).expect("expected valid dial");
+        let instructions = &rotations(&[4, 10, -3, -25, 2]);
+
+        // when
+        let landed = rot_and_count_0(&dial, instructions);
+        let passed = clilb(&dial, instructions);
+
+        // then
+        // positions: 3 -> 7 -> 7 -> 4 -> 9 -> 1
+        assert_eq!(landed, 2);
+        // 7, 7 (full turn), and 7 twice while turning back 25 from 4
+        assert_eq!(passed, 4.into());
+    }
+
+    #[test]
+    fn parse_input_understands_extended_grammar() {
+        // given
+        let input = "# warm up\nR10x3\n\n=37 # jump\n  L5  \n";
+
+        // when
+        let instructions = parse_input(input).expect("expected valid input");
+
+        // then
+        assert_eq!(
+            instructions,
+            &[
+                Instruction::Rotate(10),
+                Instruction::Rotate(10),
+                Instruction::Rotate(10),
+                Instruction::Set(37),
+                Instruction::Rotate(-5),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_input_with_origins_keeps_lines_and_repetitions() {
+        // given
+        let input = "R10x3\n# comment\nL5\n";
+
+        // when
+        let origins: Vec<String> = parse_input_with_origins(input)
+            .expect("expected valid input")
+            .iter()
+            .map(|(origin, _)| origin.to_string())
+            .collect();
+
+        // then
+        assert_eq!(
+            origins,
+            [
+                "line 1, repetition 1 of 3",
+                "line 1, repetition 2 of 3",
+                "line 1, repetition 3 of 3",
+                "line 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_input_names_line_of_error() {
+        // given
+        let input = "R10\n# fine so far\nR10x\n";
+
+        // when
+        let result = parse_input(input);
+
+        // then
+        let err = result.expect_err("expected parse error");
+        assert!(err.starts_with("line 3:"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_input_rejects_too_many_repetitions() {
+        // given
+        let input = "R10\nR10x100000000000\n";
+
+        // when
+        let result = parse_input(input);
+
+        // then
+        let err = result.expect_err("expected parse error");
+        assert!(err.starts_with("line 2:"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn counting_works_for_repetitions_and_absolute_positions() {
+        // given
+        let instructions =
+            parse_input("R25x4\n=0\n=0\nR50\n=75\nL75\n").expect("expected valid input");
+
+        // when
+        let landed = rot_and_count_0(&Dial::default(), &instructions);
+        let passed = clilb(&Dial::default(), &instructions);
+
+        // then
+        // positions: 50 -> 75 -> 0 -> 25 -> 50 -> 0 -> 0 -> 50 -> 75 -> 0
+        assert_eq!(landed, 4);
+        // setting the dial to 0 while it is already there does not count
+        assert_eq!(passed, 3.into());
+    }
+
+    #[test]
+    fn write_trace_writes_csv_records() {
+        // given
+        let instructions = &rotations(&[-68, 230, -18]);
+
+        // when
+        let mut out: Vec<u8> = Vec::new();
+        write_trace(&mut out, TraceFormat::Csv, &Dial::default(), instructions)
+            .expect("expected trace to be written");
+
+        // then
+        let trace = String::from_utf8(out).expect("expected UTF-8 output");
+        assert_eq!(
+            trace,
+            "step,start,end,wraps,crossings,count\n0,50,82,0,1,1\n1,82,12,2,3,4\n2,12,94,0,1,5\n"
+        );
+    }
+
+    #[test]
+    fn write_trace_writes_json_lines() {
+        // given
+        let instructions = &rotations(&[50]);
+
+        // when
+        let mut out: Vec<u8> = Vec::new();
+        write_trace(
+            &mut out,
+            TraceFormat::JsonLines,
+            &Dial::default(),
+            instructions,
+        )
+        .expect("expected trace to be written");
+
+        // then
+        let trace = String::from_utf8(out).expect("expected UTF-8 output");
+        assert_eq!(
+            trace,
+            "{\"step\":0,\"start\":50,\"end\":0,\"wraps\":0,\"crossings\":1,\"count\":1}\n"
+        );
+    }
+
+    #[test]
+    fn stream_passwords_writes_running_passwords() {
+        // given
+        let input = "L68\n# comment\nL30\nR48x2\n";
+
+        // when
+        let mut out: Vec<u8> = Vec::new();
+        let passwords = stream_passwords(&Dial::default(), input.as_bytes(), &mut out)
+            .expect("expected valid input");
+
+        // then
+        assert_eq!(passwords, (1.into(), 2.into()));
+        let out = String::from_utf8(out).expect("expected UTF-8 output");
+        assert_eq!(out, "1: 0 1\n3: 0 1\n4: 1 2\n");
+    }
+
+    #[test]
+    fn stream_passwords_matches_batch_counting() {
+        // given
+        let instructions = parse_input(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let passwords =
+            stream_passwords(&Dial::default(), EXAMPLE_INPUT.as_bytes(), &mut io::sink())
+                .expect("expected valid input");
+
+        // then
+        assert_eq!(
+            passwords,
+            (
+                (rot_and_count_0(&Dial::default(), &instructions) as u128).into(),
+                clilb(&Dial::default(), &instructions)
+            )
+        );
+    }
+
+    #[test]
+    fn stream_passwords_handles_huge_repetitions() {
+        // given
+        let input = "R1x1000000000000000000\n";
+        let dial = Dial::new(1, 0, 0).expect("expected valid dial");
+        let huge = format!("{}x{}\n", Instruction::Rotate(i128::MAX), u64::MAX);
+
+        // when
+        let passwords = stream_passwords(&Dial::default(), input.as_bytes(), &mut io::sink())
+            .expect("expected valid input");
+        let huge_passwords = stream_passwords(&dial, huge.as_bytes(), &mut io::sink())
+            .expect("expected valid input");
+
+        // then
+        assert_eq!(passwords, (10u128.pow(16).into(), 10u128.pow(16).into()));
+        assert_eq!(huge_passwords.0, (u64::MAX as u128).into());
+        assert_eq!(
+            huge_passwords.1.to_string(),
+            "3138550867693340381747753528143363976301043674442423599105"
+        );
+    }
+
+    #[test]
+    fn repeat_agrees_with_single_steps() {
+        for size in 1..=6 {
+            for (from, mark) in (0..size).flat_map(|from| (0..size).map(move |mark| (from, mark))) {
+                let dial = Dial::new(size, from, mark).expect("expected valid dial");
+                let instructions = (-15..=15)
+                    .map(Instruction::Rotate)
+                    .chain((-7..=7).map(Instruction::Set));
+                for inst in instructions {
+                    for times in 0..15 {
+                        // given
+                        let mut expected = Repetition {
+                            to: from,
+                            landed: 0,
+                            hits: HitCount::default(),
+                        };
+                        for _ in 0..times {
+                            let step = dial.apply(expected.to, inst);
+                            expected.to = step.to;
+                            expected.landed += (step.to == mark) as u128;
+                            expected.hits += step.hits;
+                        }
+
+                        // when
+                        let repetition = dial.repeat(from, inst, times);
+
+                        // then
+                        assert_eq!(repetition, expected, "{inst} x{times} differs on {dial:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_args_derives_start_from_size() {
+        // given
+        let args = ["--size", "10", "--mark", "3", "input.txt"].map(str::to_owned);
+
+        // when
+        let options = parse_args(args.into_iter()).expect("expected valid arguments");
+
+        // then
+        assert_eq!(options.filename, "input.txt");
+        assert_eq!(
+            options.dial,
+            Dial::new(10, 5, 3).expect("expected valid dial")
+        );
+    }
+
+    #[test]
+    fn parse_args_checks_dials() {
+        let cases: [&[&str]; 5] = [
+            &["--dials", "3"],
+            &["--dials", "0"],
+            &["--dials", "18446744073709551615"],
+            &["--dials", "2", "--trace", "csv"],
+            &["--dials", "2", "--counting", "landing"],
+        ];
+        for (i, args) in cases.into_iter().enumerate() {
+            // given
+            let args = args.iter().chain(&["input.txt"]).map(|arg| arg.to_string());
+
+            // when
+            let options = parse_args(args);
+
+            // then
+            assert_eq!(options.is_ok(), i == 0, "unexpected result for {i}");
+        }
+    }
+
+    #[test]
+    fn parse_args_rejects_start_outside_of_dial() {
+        // given
+        let args = ["--size", "10", "--start", "10", "input.txt"].map(str::to_owned);
 
         // when
-        let password = clilb(instructions);
+        let result = parse_args(args.into_iter());
 
         // then
-        assert_eq!(password, 21);
+        assert!(result.is_err());
     }
 }
@@ -0,0 +1,180 @@
+use super::{Dial, HitCount, Instruction, MAX_INSTRUCTIONS, parse_line};
+use std::iter;
+
+// A chain of dials with the same geometry. Whenever dial i rolls over from the last position
+// to position 0, dial i + 1 turns one click forward (and one click backward when rolling over
+// in the other direction), just like an odometer.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Odometer {
+    dial: Dial,
+    positions: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OdometerHits {
+    // how often each dial pointed at the mark, counted like `clilb` does
+    pub per_dial: Vec<HitCount>,
+    // after how many instructions all dials pointed at the mark at the same time
+    pub combined: usize,
+}
+
+impl Odometer {
+    pub fn new(dial: Dial, n_dials: usize) -> Odometer {
+        Odometer {
+            dial,
+            positions: vec![dial.start; n_dials],
+        }
+    }
+
+    fn apply(&mut self, target: usize, inst: Instruction, hits: &mut [HitCount]) {
+        let mut carry = match inst {
+            Instruction::Rotate(clicks) => clicks,
+            Instruction::Set(_) => {
+                let step = self.dial.apply(self.positions[target], inst);
+                self.positions[target] = step.to;
+                hits[target] += step.hits;
+                return;
+            }
+        };
+        let size = self.dial.size as i128;
+        for (pos, dial_hits) in self.positions[target..].iter_mut().zip(&mut hits[target..]) {
+            if carry == 0 {
+                break;
+            }
+            let from = *pos;
+            let step = self.dial.rotate(from, carry);
+            *pos = step.to;
+            *dial_hits += step.hits;
+            // number of times we rolled over position 0, split up to avoid overflows
+            carry = carry.div_euclid(size) + (from as i128 + carry.rem_euclid(size)) / size;
+        }
+    }
+
+    fn all_at_mark(&self) -> bool {
+        self.positions.iter().all(|pos| *pos == self.dial.mark)
+    }
+}
+
+pub fn count_odometer_hits(
+    odometer: &mut Odometer,
+    instructions: &[(usize, Instruction)],
+) -> OdometerHits {
+    let mut per_dial = vec![HitCount::default(); odometer.positions.len()];
+    let mut combined: usize = 0;
+    for (target, inst) in instructions {
+        odometer.apply(*target, *inst, &mut per_dial);
+        if odometer.all_at_mark() {
+            combined += 1;
+        }
+    }
+    OdometerHits { per_dial, combined }
+}
+
+pub fn parse_odometer_input(
+    input: &str,
+    n_dials: usize,
+) -> Result<Vec<(usize, Instruction)>, String> {
+    let mut instructions: Vec<(usize, Instruction)> = Vec::with_capacity(input.lines().count());
+    for (i, line) in input.lines().enumerate() {
+        if let Some((target, instruction, times)) =
+            parse_odometer_line(line, n_dials).map_err(|e| format!("line {}: {e}", i + 1))?
+        {
+            if times > MAX_INSTRUCTIONS - instructions.len() {
+                return Err(format!(
+                    "line {}: more than {MAX_INSTRUCTIONS} instructions after repetitions",
+                    i + 1
+                ));
+            }
+            instructions.extend(iter::repeat_n((target, instruction), times));
+        }
+    }
+    Ok(instructions)
+}
+
+// instructions may be prefixed with the dial they target, e.g. `2:R15`, the default is dial 0
+fn parse_odometer_line(
+    line: &str,
+    n_dials: usize,
+) -> Result<Option<(usize, Instruction, usize)>, String> {
+    let (target, inst) = match line.split_once(':') {
+        Some((target, inst)) if !target.contains('#') => (
+            target
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| format!("unable to parse dial number '{target}': {e}"))?,
+            inst,
+        ),
+        _ => (0, line),
+    };
+    if target >= n_dials {
+        return Err(format!(
+            "dial {target} does not exist, there are only {n_dials} dials"
+        ));
+    }
+    Ok(parse_line(inst)?.map(|(instruction, times)| (target, instruction, times)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_odometer_hits_carries_over_to_next_dials() {
+        // given
+        let dial = Dial::new(10, 0, 0).expect("expected valid dial");
+        let mut odometer = Odometer::new(dial, 3);
+        let instructions =
+            parse_odometer_input("R5\n1:R4\nR96\n2:L1\n0:R900", 3).expect("expected valid input");
+
+        // when
+        let hits = count_odometer_hits(&mut odometer, &instructions);
+
+        // then
+        // readings (dial 2, 1, 0): 005, 045, 141, 041, 941
+        assert_eq!(odometer.positions, vec![1, 4, 9]);
+        assert_eq!(hits.per_dial, vec![100.into(), 10.into(), 1.into()]);
+        assert_eq!(hits.combined, 0);
+    }
+
+    #[test]
+    fn count_odometer_hits_counts_combined_state() {
+        // given
+        let mut odometer = Odometer::new(Dial::default(), 2);
+        let instructions =
+            parse_odometer_input("1:R50\nR50\n1:L1 # both at 0 now\n=50\n1:L100\n=0", 2)
+                .expect("expected valid input");
+
+        // when
+        let hits = count_odometer_hits(&mut odometer, &instructions);
+
+        // then
+        assert_eq!(hits.combined, 2);
+        assert_eq!(hits.per_dial, vec![2.into(), 3.into()]);
+    }
+
+    #[test]
+    fn parse_odometer_input_rejects_unknown_dials() {
+        // given
+        let input = "R1\n2:R1\n";
+
+        // when
+        let result = parse_odometer_input(input, 2);
+
+        // then
+        let err = result.expect_err("expected parse error");
+        assert!(err.starts_with("line 2:"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_odometer_input_rejects_too_many_repetitions() {
+        // given
+        let input = "R1\n# many\n1:R10x100000000000\n";
+
+        // when
+        let result = parse_odometer_input(input, 2);
+
+        // then
+        let err = result.expect_err("expected parse error");
+        assert!(err.starts_with("line 3:"), "unexpected error: {err}");
+    }
+}
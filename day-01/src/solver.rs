@@ -0,0 +1,545 @@
+use super::{Counting, Dial, Instruction};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edit {
+    Flip,
+    // replaces a rotation by the given one, which may also go the other way for `R0`
+    Magnitude(i128),
+    Delete,
+}
+
+impl Edit {
+    // the instruction after the edit, or `None` if it was deleted
+    pub fn apply(self, inst: Instruction) -> Option<Instruction> {
+        match (self, inst) {
+            (Edit::Delete, _) => None,
+            (Edit::Flip, Instruction::Rotate(clicks)) => Some(Instruction::Rotate(-clicks)),
+            (Edit::Magnitude(clicks), _) => Some(Instruction::Rotate(clicks)),
+            (Edit::Flip, inst) => Some(inst),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Change {
+    // index into the (expanded) instruction list
+    pub index: usize,
+    pub edit: Edit,
+}
+
+// We store one layer of costs per instruction for every position and every number of
+// hits we still need, so we need some limit on how much memory this may take.
+const MAX_CELLS: usize = 1 << 27;
+
+const UNREACHABLE: u32 = u32::MAX;
+
+// Finds the fewest changes to single instructions so that counting the hits results in
+// `target`. Returns `None` if the target cannot be reached at all.
+//
+// This is a dynamic program over (instruction, position, hits still needed) that runs in
+// O(instructions × dial size × target). Changing the magnitude of a rotation can land on
+// any position with any number of extra full turns, these transitions are handled with
+// running minima instead of trying every magnitude. To keep the memory down, only every
+// √n-th layer is kept and the layers in between are recomputed when collecting the edits.
+pub fn find_edits(
+    dial: &Dial,
+    instructions: &[Instruction],
+    counting: Counting,
+    target: usize,
+) -> Result<Option<Vec<Change>>, String> {
+    let solver = Solver {
+        dial,
+        counting,
+        size: usize::try_from(dial.size).map_err(|e| e.to_string())?,
+        width: target
+            .checked_add(1)
+            .ok_or_else(|| format!("target {target} is too large"))?,
+    };
+    let block = instructions.len().isqrt().max(1);
+    let layers_in_memory = instructions.len() / block + 1 + block;
+    if solver
+        .size
+        .checked_mul(solver.width)
+        .and_then(|cells| cells.checked_mul(layers_in_memory))
+        .is_none_or(|cells| cells > MAX_CELLS)
+    {
+        return Err(format!(
+            "searching for {target} hits on a dial of size {} takes too much memory",
+            dial.size
+        ));
+    }
+
+    // checkpoints[j] holds the layer for instruction min((j + 1) * block, len), the one right
+    // after block j
+    let mut checkpoints: Vec<Vec<u32>> = Vec::with_capacity(layers_in_memory);
+    let mut layer = solver.final_layer();
+    for i in (0..instructions.len()).rev() {
+        if (i + 1).is_multiple_of(block) || i + 1 == instructions.len() {
+            checkpoints.push(layer.clone());
+        }
+        layer = solver.back_layer(instructions[i], &layer);
+    }
+    let mut cost = layer[solver.cell(dial.start as usize, target)];
+    if cost == UNREACHABLE {
+        return Ok(None);
+    }
+    checkpoints.reverse();
+
+    let mut changes: Vec<Change> = Vec::new();
+    let mut pos = dial.start as usize;
+    let mut needed = target;
+    for block_start in (0..instructions.len()).step_by(block) {
+        let block_end = (block_start + block).min(instructions.len());
+        // layers[j] is the layer for instruction block_start + j + 1
+        let mut layers: Vec<Vec<u32>> = Vec::with_capacity(block_end - block_start);
+        let mut layer = checkpoints[block_start / block].clone();
+        for i in (block_start + 1..block_end).rev() {
+            let prev = solver.back_layer(instructions[i], &layer);
+            layers.push(layer);
+            layer = prev;
+        }
+        layers.push(layer);
+        layers.reverse();
+
+        for (i, next) in (block_start..block_end).zip(&layers) {
+            let (edit, to, hits) = solver.choose(instructions[i], next, pos, needed, cost);
+            if let Some(edit) = edit {
+                changes.push(Change { index: i, edit });
+                cost -= 1;
+            }
+            pos = to;
+            needed -= hits;
+        }
+    }
+    Ok(Some(changes))
+}
+
+struct Solver<'a> {
+    dial: &'a Dial,
+    counting: Counting,
+    size: usize,
+    // number of different hit counts we keep track of, i.e. target + 1
+    width: usize,
+}
+
+impl Solver<'_> {
+    fn cell(&self, pos: usize, needed: usize) -> usize {
+        pos * self.width + needed
+    }
+
+    fn final_layer(&self) -> Vec<u32> {
+        let mut layer = vec![UNREACHABLE; self.size * self.width];
+        for pos in 0..self.size {
+            layer[self.cell(pos, 0)] = 0;
+        }
+        layer
+    }
+
+    // the new position and the number of hits, if they are not too many
+    fn apply(&self, pos: usize, inst: Option<Instruction>) -> Option<(usize, usize)> {
+        let Some(inst) = inst else {
+            // deleted instructions don't move the dial and don't count
+            return Some((pos, 0));
+        };
+        let step = self.dial.apply(pos as i64, inst);
+        let hits = match self.counting {
+            Counting::Landing => (step.to == self.dial.mark) as u128,
+            Counting::Passing => step.hits,
+        };
+        usize::try_from(hits)
+            .ok()
+            .filter(|hits| *hits < self.width)
+            .map(|hits| (step.to as usize, hits))
+    }
+
+    // the edits we can make to an instruction with the result, except for the magnitude
+    fn simple_edits(&self, inst: Instruction) -> Vec<(Option<Edit>, Option<Instruction>)> {
+        let mut edits = vec![(None, Some(inst)), (Some(Edit::Delete), None)];
+        if let Instruction::Rotate(clicks) = inst
+            && clicks != 0
+        {
+            edits.push((Some(Edit::Flip), Some(Instruction::Rotate(-clicks))));
+        }
+        edits
+    }
+
+    // directions in which the magnitude of an instruction may be changed
+    fn magnitude_directions(&self, inst: Instruction) -> &'static [i128] {
+        match inst {
+            Instruction::Rotate(0) => &[1, -1],
+            Instruction::Rotate(clicks) if clicks > 0 => &[1],
+            Instruction::Rotate(_) => &[-1],
+            Instruction::Set(_) => &[],
+        }
+    }
+
+    // position that is `rel` clicks away from the mark in the given direction
+    fn pos_after_mark(&self, rel: usize, direction: i128) -> usize {
+        let rel = rel as i64 * direction as i64;
+        (self.dial.mark + rel).rem_euclid(self.dial.size) as usize
+    }
+
+    fn back_layer(&self, inst: Instruction, next: &[u32]) -> Vec<u32> {
+        let mut layer = vec![UNREACHABLE; next.len()];
+        let simple_edits = self.simple_edits(inst);
+        for pos in 0..self.size {
+            for (edit, inst) in simple_edits.iter().copied() {
+                let Some((to, hits)) = self.apply(pos, inst) else {
+                    continue;
+                };
+                let cost = edit.is_some() as u32;
+                for needed in hits..self.width {
+                    let next_cost = next[self.cell(to, needed - hits)];
+                    let cell = &mut layer[self.cell(pos, needed)];
+                    *cell = (*cell).min(next_cost.saturating_add(cost));
+                }
+            }
+        }
+        let directions = self.magnitude_directions(inst);
+        if directions.is_empty() {
+            return layer;
+        }
+
+        match self.counting {
+            Counting::Landing => {
+                // extra full turns don't matter, every position can be reached from everywhere
+                let mut best = vec![UNREACHABLE; self.width];
+                for to in 0..self.size {
+                    let hits = (to as i64 == self.dial.mark) as usize;
+                    for needed in hits..self.width {
+                        best[needed] = best[needed].min(next[self.cell(to, needed - hits)]);
+                    }
+                }
+                for pos in 0..self.size {
+                    for needed in 0..self.width {
+                        let cell = &mut layer[self.cell(pos, needed)];
+                        *cell = (*cell).min(best[needed].saturating_add(1));
+                    }
+                }
+            }
+            Counting::Passing => {
+                // every extra full turn is an extra hit, so we may use up to `needed` hits
+                let mut at_most = next.to_vec();
+                for pos in 0..self.size {
+                    for needed in 1..self.width {
+                        let cell = self.cell(pos, needed);
+                        at_most[cell] = at_most[cell].min(at_most[cell - 1]);
+                    }
+                }
+                for direction in directions.iter().copied() {
+                    self.magnitude_passing(direction, &at_most, &mut layer);
+                }
+            }
+        }
+        layer
+    }
+
+    // Rotating from `rel` clicks after the mark to `rel_to` clicks after the mark with less
+    // than one full turn hits the mark exactly once if `rel_to <= rel` and not at all otherwise.
+    fn magnitude_passing(&self, direction: i128, at_most: &[u32], layer: &mut [u32]) {
+        let mut best = vec![UNREACHABLE; self.width];
+        for rel in 0..self.size {
+            let pos = self.pos_after_mark(rel, direction);
+            for needed in 0..self.width {
+                best[needed] = best[needed].min(at_most[self.cell(pos, needed)]);
+            }
+            for needed in 1..self.width {
+                let cell = &mut layer[self.cell(pos, needed)];
+                *cell = (*cell).min(best[needed - 1].saturating_add(1));
+            }
+        }
+        best.fill(UNREACHABLE);
+        for rel in (0..self.size).rev() {
+            let pos = self.pos_after_mark(rel, direction);
+            for needed in 0..self.width {
+                let cell = &mut layer[self.cell(pos, needed)];
+                *cell = (*cell).min(best[needed].saturating_add(1));
+            }
+            for needed in 0..self.width {
+                best[needed] = best[needed].min(at_most[self.cell(pos, needed)]);
+            }
+        }
+    }
+
+    // Picks an edit for the instruction that can reach the needed hits with `cost` edits
+    // in total. Returns it with the new position and the hits.
+    fn choose(
+        &self,
+        inst: Instruction,
+        next: &[u32],
+        pos: usize,
+        needed: usize,
+        cost: u32,
+    ) -> (Option<Edit>, usize, usize) {
+        for (edit, inst) in self.simple_edits(inst) {
+            if let Some((to, hits)) = self.apply(pos, inst)
+                && hits <= needed
+                && next[self.cell(to, needed - hits)].saturating_add(edit.is_some() as u32) == cost
+            {
+                return (edit, to, hits);
+            }
+        }
+        for direction in self.magnitude_directions(inst).iter().copied() {
+            for distance in 1..=self.size {
+                let magnitude = distance as u128;
+                let rotation = Instruction::Rotate(direction * magnitude as i128);
+                let Some((to, hits)) = self.apply(pos, Some(rotation)) else {
+                    continue;
+                };
+                let extra_turns = match self.counting {
+                    Counting::Landing => 0..=0,
+                    Counting::Passing => 0..=needed.saturating_sub(hits),
+                };
+                for extra in extra_turns {
+                    if hits + extra <= needed
+                        && next[self.cell(to, needed - hits - extra)].saturating_add(1) == cost
+                    {
+                        let magnitude = magnitude + extra as u128 * self.size as u128;
+                        let clicks = direction * magnitude as i128;
+                        return (Some(Edit::Magnitude(clicks)), to, hits + extra);
+                    }
+                }
+            }
+        }
+        unreachable!("the cost of an instruction must come from one of its edits")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{clilb, clilb_by_clicks, parse_input, rot_and_count_0};
+
+    fn apply_changes(instructions: &[Instruction], changes: &[Change]) -> Vec<Instruction> {
+        let mut instructions: Vec<Option<Instruction>> =
+            instructions.iter().copied().map(Some).collect();
+        for change in changes {
+            instructions[change.index] =
+                instructions[change.index].and_then(|inst| change.edit.apply(inst));
+        }
+        instructions.into_iter().flatten().collect()
+    }
+
+    static EXAMPLE_INPUT: &str = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n";
+
+    #[test]
+    fn find_edits_needs_no_edits_for_actual_password() {
+        // given
+        let instructions = parse_input(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let changes = find_edits(&Dial::default(), &instructions, Counting::Passing, 6)
+            .expect("expected search to work");
+
+        // then
+        assert_eq!(changes, Some(vec![]));
+    }
+
+    #[test]
+    fn find_edits_finds_fewest_edits_for_passing() {
+        // given
+        let instructions = parse_input(EXAMPLE_INPUT).expect("expected valid input");
+
+        for target in [0, 1, 5, 7, 12, 250] {
+            // when
+            let changes = find_edits(&Dial::default(), &instructions, Counting::Passing, target)
+                .expect("expected search to work")
+                .expect("expected a solution");
+
+            // then
+            let edited = apply_changes(&instructions, &changes);
+            assert_eq!(clilb(&Dial::default(), &edited), (target as u128).into());
+            // a single edit can add as many full turns as we want
+            if target > 6 {
+                assert_eq!(changes.len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn find_edits_finds_fewest_edits_for_landing() {
+        // given
+        let instructions = parse_input(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let changes = find_edits(&Dial::default(), &instructions, Counting::Landing, 5)
+            .expect("expected search to work")
+            .expect("expected a solution");
+
+        // then
+        let edited = apply_changes(&instructions, &changes);
+        assert_eq!(rot_and_count_0(&Dial::default(), &edited), 5);
+        assert_eq!(changes.len(), 2);
+        // make sure a single edit really is not enough, the magnitude only matters modulo 100
+        for index in 0..instructions.len() {
+            let edits = [Edit::Delete, Edit::Flip]
+                .into_iter()
+                .chain((-100..=100).map(Edit::Magnitude));
+            for edit in edits {
+                let edited = apply_changes(&instructions, &[Change { index, edit }]);
+                assert_ne!(rot_and_count_0(&Dial::default(), &edited), 5);
+            }
+        }
+    }
+
+    #[test]
+    fn find_edits_keeps_direction_of_zero_rotations() {
+        // given
+        let dial = Dial::new(7, 1, 2).expect("expected valid dial");
+        let instructions = parse_input("R0\nR1\nR2\nL5\n").expect("expected valid input");
+
+        // when
+        let changes = find_edits(&dial, &instructions, Counting::Passing, 0)
+            .expect("expected search to work")
+            .expect("expected a solution");
+
+        // then
+        assert_eq!(
+            changes,
+            vec![Change {
+                index: 0,
+                edit: Edit::Magnitude(-3)
+            }]
+        );
+        assert_eq!(
+            clilb(&dial, &apply_changes(&instructions, &changes)),
+            0.into()
+        );
+    }
+
+    fn count(dial: &Dial, instructions: &[Instruction], counting: Counting) -> u128 {
+        match counting {
+            Counting::Landing => rot_and_count_0(dial, instructions) as u128,
+            Counting::Passing => clilb_by_clicks(dial, instructions).low,
+        }
+    }
+
+    // Tries every way to edit up to `budget` instructions. Rotating further than
+    // `size * (target + 1)` clicks always hits the mark too often or is the same as a shorter
+    // rotation, so those magnitudes are enough.
+    fn reachable_with_edits(
+        dial: &Dial,
+        instructions: &[Instruction],
+        counting: Counting,
+        target: usize,
+        budget: usize,
+        edited: &mut Vec<Instruction>,
+    ) -> bool {
+        let Some((inst, rest)) = instructions.split_first() else {
+            return count(dial, edited, counting) == target as u128;
+        };
+        let reachable = |edited: &mut Vec<Instruction>, inst: Option<Instruction>, cost| {
+            let len = edited.len();
+            edited.extend(inst);
+            let reachable =
+                reachable_with_edits(dial, rest, counting, target, budget - cost, edited);
+            edited.truncate(len);
+            reachable
+        };
+        if reachable(edited, Some(*inst), 0) {
+            return true;
+        }
+        if budget == 0 {
+            return false;
+        }
+        let mut variants: Vec<Option<Instruction>> = vec![None];
+        if let Instruction::Rotate(clicks) = *inst {
+            let max_clicks = dial.size as i128 * (target as i128 + 1);
+            let directions: &[i128] = match clicks {
+                0 => &[1, -1],
+                _ => &[clicks.signum()],
+            };
+            variants.push(Some(Instruction::Rotate(-clicks)));
+            for direction in directions {
+                variants.extend(
+                    (1..=max_clicks)
+                        .map(|m| direction * m)
+                        .filter(|m| *m != clicks)
+                        .map(|m| Some(Instruction::Rotate(m))),
+                );
+            }
+        }
+        variants
+            .into_iter()
+            .any(|variant| reachable(edited, variant, 1))
+    }
+
+    fn fewest_edits_by_brute_force(
+        dial: &Dial,
+        instructions: &[Instruction],
+        counting: Counting,
+        target: usize,
+    ) -> Option<usize> {
+        (0..=instructions.len()).find(|budget| {
+            reachable_with_edits(dial, instructions, counting, target, *budget, &mut vec![])
+        })
+    }
+
+    #[test]
+    fn find_edits_finds_fewest_edits_for_all_small_cases() {
+        // given
+        let mut cases: Vec<Vec<Instruction>> = vec![vec![]];
+        let mut shorter: Vec<Vec<Instruction>> = vec![vec![]];
+        for _ in 0..3 {
+            shorter = shorter
+                .iter()
+                .flat_map(|case| {
+                    (-4..=4).map(|clicks| {
+                        let mut case = case.clone();
+                        case.push(Instruction::Rotate(clicks));
+                        case
+                    })
+                })
+                .collect();
+            cases.extend(shorter.iter().cloned());
+        }
+        for size in 1..=3 {
+            for (start, mark) in
+                (0..size).flat_map(|start| (0..size).map(move |mark| (start, mark)))
+            {
+                let dial = Dial::new(size, start, mark).expect("expected valid dial");
+                for instructions in &cases {
+                    for (counting, target) in [Counting::Landing, Counting::Passing]
+                        .into_iter()
+                        .flat_map(|counting| (0..4).map(move |target| (counting, target)))
+                    {
+                        // when
+                        let changes = find_edits(&dial, instructions, counting, target)
+                            .expect("expected search to work");
+
+                        // then
+                        let fewest =
+                            fewest_edits_by_brute_force(&dial, instructions, counting, target);
+                        assert_eq!(
+                            changes.as_ref().map(Vec::len),
+                            fewest,
+                            "{changes:?} are not the fewest edits for {target} on {dial:?}, {instructions:?} and {counting:?}"
+                        );
+                        let Some(changes) = changes else {
+                            continue;
+                        };
+                        let edited = apply_changes(instructions, &changes);
+                        assert_eq!(
+                            count(&dial, &edited, counting),
+                            target as u128,
+                            "{changes:?} miss {target} for {dial:?}, {instructions:?} and {counting:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn find_edits_detects_unreachable_target() {
+        // given
+        let instructions = parse_input("R1\nR1\n").expect("expected valid input");
+
+        // when
+        let changes = find_edits(&Dial::default(), &instructions, Counting::Landing, 3)
+            .expect("expected search to work");
+
+        // then
+        assert_eq!(changes, None);
+    }
+}
@@ -0,0 +1,117 @@
+use super::{Counting, Dial, HitCount, Instruction, steps};
+use std::fmt::Write;
+
+const MARGIN: f64 = 40.0;
+const PLOT_HEIGHT: f64 = 300.0;
+const MAX_PLOT_WIDTH: f64 = 2000.0;
+
+// Renders the dial position after every instruction as a line. Landing on the mark is
+// drawn as a red dot. When counting every click, passing the mark during a rotation is
+// drawn as an orange diamond on the mark line, hover over it to see how often it passed.
+pub fn render_svg(dial: &Dial, instructions: &[Instruction], counting: Counting) -> String {
+    let dx = (MAX_PLOT_WIDTH / instructions.len().max(1) as f64).clamp(1.0, 20.0);
+    let width = 2.0 * MARGIN + dx * instructions.len() as f64;
+    let height = 2.0 * MARGIN + PLOT_HEIGHT;
+    let x = |i: usize| MARGIN + dx * i as f64;
+    let y = |pos: i64| {
+        if dial.size == 1 {
+            MARGIN + PLOT_HEIGHT / 2.0
+        } else {
+            MARGIN + PLOT_HEIGHT * (dial.size - 1 - pos) as f64 / (dial.size - 1) as f64
+        }
+    };
+
+    let mut points = format!("{:.1},{:.1}", x(0), y(dial.start));
+    let mut landings = String::new();
+    let mut crossings = String::new();
+    let mut count = HitCount::default();
+    for (i, step) in steps(dial, instructions).enumerate() {
+        let (px, py) = (x(i + 1), y(step.to));
+        // writing to a String does not fail
+        let _ = write!(points, " {px:.1},{py:.1}");
+        let landed = step.to == dial.mark;
+        count += match counting {
+            Counting::Landing => landed as u128,
+            Counting::Passing => step.hits,
+        };
+        if landed {
+            let _ = write!(
+                landings,
+                r#"<circle cx="{px:.1}" cy="{py:.1}" r="3" fill="red"><title>instruction {}</title></circle>"#,
+                i + 1
+            );
+        }
+        // setting the dial to the mark while it is already there is no hit
+        let passed = step.hits.saturating_sub(landed as u128);
+        if counting == Counting::Passing && passed > 0 {
+            let (cx, cy) = (px - dx / 2.0, y(dial.mark));
+            let _ = write!(
+                crossings,
+                r#"<path d="M{:.1},{cy:.1} L{cx:.1},{:.1} L{:.1},{cy:.1} L{cx:.1},{:.1} Z" fill="orange"><title>instruction {}: passed {passed} times</title></path>"#,
+                cx - 4.0,
+                cy - 4.0,
+                cx + 4.0,
+                cy + 4.0,
+                i + 1
+            );
+        }
+    }
+
+    let mode = match counting {
+        Counting::Landing => "landing",
+        Counting::Passing => "passing",
+    };
+    let mark_y = y(dial.mark);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.0}" height="{height:.0}" viewBox="0 0 {width:.0} {height:.0}">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(
+        svg,
+        r#"<text x="{MARGIN}" y="{:.0}" font-family="sans-serif" font-size="14">dial of size {}, counting {mode} on {}: {count}</text>"#,
+        MARGIN / 2.0,
+        dial.size,
+        dial.mark
+    );
+    let _ = writeln!(
+        svg,
+        r#"<line x1="{MARGIN}" y1="{mark_y:.1}" x2="{:.1}" y2="{mark_y:.1}" stroke="gray" stroke-dasharray="4 4"/>"#,
+        width - MARGIN
+    );
+    let _ = writeln!(
+        svg,
+        r#"<polyline points="{points}" fill="none" stroke="steelblue" stroke-width="1"/>"#
+    );
+    let _ = writeln!(svg, "{crossings}");
+    let _ = writeln!(svg, "{landings}");
+    let _ = writeln!(svg, "</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_input;
+
+    #[test]
+    fn render_svg_highlights_landings_and_crossings() {
+        // given
+        let instructions = parse_input("L50\nR150\nL60\nR10\n=0").expect("expected valid input");
+
+        // when
+        let landing = render_svg(&Dial::default(), &instructions, Counting::Landing);
+        let passing = render_svg(&Dial::default(), &instructions, Counting::Passing);
+
+        // then
+        assert_eq!(landing.matches("<circle").count(), 3);
+        assert_eq!(landing.matches("<path").count(), 0);
+        assert!(landing.contains("counting landing on 0: 3"));
+
+        assert_eq!(passing.matches("<circle").count(), 3);
+        assert!(passing.contains("instruction 2: passed 1 times"));
+        assert!(passing.contains("instruction 3: passed 1 times"));
+        assert!(passing.contains("counting passing on 0: 4"));
+    }
+}
@@ -0,0 +1,197 @@
+use super::{IdRange, Rule, format_radix, repeated_ids, repunit};
+use std::fmt::Write;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExplainFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for ExplainFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(ExplainFormat::Table),
+            "json" => Ok(ExplainFormat::Json),
+            _ => Err("expected 'table' or 'json'".to_owned()),
+        }
+    }
+}
+
+// why an ID is invalid: it is `block` repeated `repetitions` times, where the block
+// itself is not repeated again
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Explanation {
+    pub id: u128,
+    pub block: u128,
+    pub repetitions: u32,
+}
+
+impl Explanation {
+    // the names of the checks from the puzzle that reject the ID
+    fn rules(&self) -> &'static [&'static str] {
+        // an even number of repetitions can be split into two equal halves
+        if self.repetitions.is_multiple_of(2) {
+            &["is_invalid", "is_invalid_2"]
+        } else {
+            &["is_invalid_2"]
+        }
+    }
+}
+
+pub fn explain_range(from: u128, to: u128, base: u32) -> Vec<Explanation> {
+    repeated_ids(from, to, Rule::PART_2, base)
+        .map(|id| {
+            let len = id.ilog(base as u128) + 1;
+            // there is always a period, at the latest the whole ID
+            let (period, r) = (1..=len)
+                .filter(|d| len.is_multiple_of(*d))
+                .filter_map(|d| Some((d, repunit(len, d, base)?)))
+                .find(|(d, r)| {
+                    id.is_multiple_of(*r)
+                        && (base as u128).checked_pow(*d).is_none_or(|p| id / r < p)
+                })
+                .unwrap_or((len, 1));
+            Explanation {
+                id,
+                block: id / r,
+                repetitions: len / period,
+            }
+        })
+        .collect()
+}
+
+pub fn format_explanations(
+    ranges: &[(IdRange, Vec<Explanation>)],
+    format: ExplainFormat,
+    base: u32,
+) -> String {
+    // writing to a String does not fail
+    let mut out = String::new();
+    match format {
+        ExplainFormat::Table => {
+            let mut rows: Vec<[String; 5]> = vec![[
+                "range".to_owned(),
+                "id".to_owned(),
+                "block".to_owned(),
+                "repetitions".to_owned(),
+                "rules".to_owned(),
+            ]];
+            for ((from, to), explanations) in ranges {
+                let range = format!("{}-{}", format_radix(*from, base), format_radix(*to, base));
+                for explanation in explanations {
+                    rows.push([
+                        range.clone(),
+                        format_radix(explanation.id, base),
+                        format_radix(explanation.block, base),
+                        explanation.repetitions.to_string(),
+                        explanation.rules().join(", "),
+                    ]);
+                }
+            }
+            let mut widths = [0; 5];
+            for row in &rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+            }
+            for row in &rows {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect();
+                let _ = writeln!(out, "{}", line.join("  ").trim_end());
+            }
+        }
+        ExplainFormat::Json => {
+            let ranges: Vec<String> = ranges
+                .iter()
+                .map(|((from, to), explanations)| {
+                    let ids: Vec<String> = explanations
+                        .iter()
+                        .map(|explanation| {
+                            let rules: Vec<String> = explanation
+                                .rules()
+                                .iter()
+                                .map(|rule| format!(r#""{rule}""#))
+                                .collect();
+                            format!(
+                                r#"{{"id":"{}","block":"{}","repetitions":{},"rules":[{}]}}"#,
+                                format_radix(explanation.id, base),
+                                format_radix(explanation.block, base),
+                                explanation.repetitions,
+                                rules.join(",")
+                            )
+                        })
+                        .collect();
+                    format!(
+                        r#"{{"from":"{}","to":"{}","invalid":[{}]}}"#,
+                        format_radix(*from, base),
+                        format_radix(*to, base),
+                        ids.join(",")
+                    )
+                })
+                .collect();
+            let _ = writeln!(out, "[{}]", ranges.join(","));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn explain_range_finds_smallest_block() {
+        // given
+        let (from, to) = (1110, 1212);
+
+        // when
+        let explanations = explain_range(from, to, 10);
+
+        // then
+        assert_eq!(
+            explanations,
+            vec![
+                Explanation {
+                    id: 1111,
+                    block: 1,
+                    repetitions: 4
+                },
+                Explanation {
+                    id: 1212,
+                    block: 12,
+                    repetitions: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn format_explanations_writes_table_and_json() {
+        // given
+        let ranges = vec![((95, 115), explain_range(95, 115, 10))];
+
+        // when
+        let table = format_explanations(&ranges, ExplainFormat::Table, 10);
+        let json = format_explanations(&ranges, ExplainFormat::Json, 10);
+
+        // then
+        assert_eq!(
+            table,
+            "range   id   block  repetitions  rules\n\
+             95-115  99   9      2            is_invalid, is_invalid_2\n\
+             95-115  111  1      3            is_invalid_2\n"
+        );
+        assert_eq!(
+            json,
+            r#"[{"from":"95","to":"115","invalid":[{"id":"99","block":"9","repetitions":2,"rules":["is_invalid","is_invalid_2"]},{"id":"111","block":"1","repetitions":3,"rules":["is_invalid_2"]}]}]"#
+                .to_owned()
+                + "\n"
+        );
+    }
+}
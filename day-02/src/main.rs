@@ -1,53 +1,462 @@
 #![forbid(unsafe_code)]
 
 use std::env;
+use std::fmt::Display;
 use std::fs::read_to_string;
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+
+mod explain;
+mod parallel;
+
+use explain::{ExplainFormat, explain_range, format_explanations};
+use parallel::sum_in_parallel;
 
 fn main() -> Result<(), String> {
-    let filename = env::args()
-        .nth(1)
-        .ok_or_else(|| "No file name given.".to_owned())?;
-    let content = read_to_string(Path::new(&filename)).map_err(|e| e.to_string())?;
-    let ids = parse(&content)?;
+    let options = parse_args(env::args().skip(1))?;
+    let content = read_to_string(Path::new(&options.filename)).map_err(|e| e.to_string())?;
+    let base = options.base;
+    let ranges = parse(&content, base)?;
 
-    let invalid_sum = sum_invalid(&ids);
-    println!("The sum of invalid IDs is {invalid_sum}.");
+    if let Some(format) = options.explain {
+        let explanations: Vec<(IdRange, Vec<_>)> = ranges
+            .iter()
+            .map(|(from, to)| ((*from, *to), explain_range(*from, *to, base)))
+            .collect();
+        print!("{}", format_explanations(&explanations, format, base));
+        return Ok(());
+    }
 
-    let invalid_sum = sum_invalid_2(&ids);
-    println!("With more silly pattern, the sum of invalid IDs is {invalid_sum}");
+    let (ids, overlaps) = join_ranges(ranges);
+    for ((from_a, to_a), (from_b, to_b)) in overlaps {
+        eprintln!(
+            "warning: ranges {}-{} and {}-{} overlap, IDs in both are only counted once",
+            format_radix(from_a, base),
+            format_radix(to_a, base),
+            format_radix(from_b, base),
+            format_radix(to_b, base)
+        );
+    }
+
+    if options.list {
+        for (from, to) in &ids {
+            for id in repeated_ids(*from, *to, options.rule.unwrap_or(Rule::PART_2), base) {
+                println!("{}", format_radix(id, base));
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(rule) = options.rule {
+        let invalid_sum = sum_invalid_with_rule(&ids, rule, base, options.evaluation)?;
+        println!(
+            "With the custom pattern, the sum of invalid IDs is {}",
+            format_radix(invalid_sum, base)
+        );
+        return Ok(());
+    }
+
+    let invalid_sum = sum_invalid(&ids, base, options.evaluation)?;
+    println!(
+        "The sum of invalid IDs is {}.",
+        format_radix(invalid_sum, base)
+    );
+
+    let invalid_sum = sum_invalid_2(&ids, base, options.evaluation)?;
+    println!(
+        "With more silly pattern, the sum of invalid IDs is {}",
+        format_radix(invalid_sum, base)
+    );
 
     Ok(())
 }
 
-fn sum_invalid(ids: &[(u64, u64)]) -> u64 {
-    ids.iter()
-        .flat_map(|(from, to)| (*from..=*to).filter(|id| is_invalid(*id)))
-        .sum()
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Options {
+    filename: String,
+    // IDs, sums and listed IDs are all written in this base
+    base: u32,
+    list: bool,
+    explain: Option<ExplainFormat>,
+    // only set if the user wants something else than the puzzle rules
+    rule: Option<Rule>,
+    evaluation: Evaluation,
+}
+
+// how the invalid IDs are summed up
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Evaluation {
+    // check every single ID instead of using the closed form, this is only feasible for
+    // ranges up to a few billion IDs
+    scan: bool,
+    threads: usize,
+}
+
+// every thread gets its own stack, the operating system won't give us arbitrarily many
+const MAX_THREADS: usize = 1024;
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut filename: Option<String> = None;
+    let mut base: u32 = 10;
+    let mut list = false;
+    let mut explain: Option<ExplainFormat> = None;
+    let mut repetitions: Option<Repetitions> = None;
+    let mut min_block: Option<u32> = None;
+    let mut scan = false;
+    let mut threads: Option<usize> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => base = parse_option_value(&arg, args.next())?,
+            "--list" => list = true,
+            "--explain" => explain = Some(parse_option_value(&arg, args.next())?),
+            "--exactly" => {
+                repetitions = Some(Repetitions::Exactly(parse_option_value(&arg, args.next())?))
+            }
+            "--at-least" => {
+                repetitions = Some(Repetitions::AtLeast(parse_option_value(&arg, args.next())?))
+            }
+            "--min-block" => min_block = Some(parse_option_value(&arg, args.next())?),
+            "--scan" => scan = true,
+            "--threads" => threads = Some(parse_option_value(&arg, args.next())?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            _ => {
+                if filename.is_some() {
+                    return Err(format!("unexpected extra argument '{arg}'"));
+                }
+                filename = Some(arg);
+            }
+        }
+    }
+
+    if !(2..=36).contains(&base) {
+        return Err(format!("base must be between 2 and 36, but is {base}"));
+    }
+    let threads = threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    if !(1..=MAX_THREADS).contains(&threads) {
+        return Err(format!(
+            "the number of threads must be between 1 and {MAX_THREADS}, but is {threads}"
+        ));
+    }
+    let rule = if repetitions.is_some() || min_block.is_some() {
+        Some(Rule::new(
+            repetitions.unwrap_or(Repetitions::AtLeast(2)),
+            min_block.unwrap_or(1),
+        )?)
+    } else {
+        None
+    };
+    Ok(Options {
+        filename: filename.ok_or_else(|| "No file name given.".to_owned())?,
+        base,
+        list,
+        explain,
+        rule,
+        evaluation: Evaluation { scan, threads },
+    })
+}
+
+fn parse_option_value<T>(option: &str, value: Option<String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| format!("option '{option}' needs a value"))?;
+    value
+        .parse()
+        .map_err(|e| format!("invalid value '{value}' for option '{option}': {e}"))
+}
+
+fn format_radix(mut n: u128, base: u32) -> String {
+    let mut digits: Vec<char> = Vec::with_capacity(128);
+    loop {
+        digits.push(char::from_digit((n % base as u128) as u32, base).unwrap_or('?'));
+        n /= base as u128;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+type IdRange = (u128, u128);
+
+// Joins overlapping and adjacent ranges, so we don't count any ID twice. Also returns all
+// overlapping pairs of ranges from the input.
+fn join_ranges(mut ranges: Vec<IdRange>) -> (Vec<IdRange>, Vec<(IdRange, IdRange)>) {
+    if ranges.is_empty() {
+        return (vec![], vec![]);
+    }
+    ranges.sort_unstable();
+    let mut current_range: IdRange = ranges[0];
+    // the input range that reaches as far as the current range, a range that starts before
+    // the end of the current range overlaps with it
+    let mut furthest: IdRange = ranges[0];
+    let mut joined: Vec<IdRange> = Vec::with_capacity(ranges.len());
+    let mut overlaps: Vec<(IdRange, IdRange)> = Vec::new();
+    for (from, to) in &ranges[1..] {
+        if *from <= current_range.1 {
+            overlaps.push((furthest, (*from, *to)));
+        }
+        if *from <= current_range.1.saturating_add(1) {
+            if *to > current_range.1 {
+                current_range.1 = *to;
+                furthest = (*from, *to);
+            }
+        } else {
+            joined.push(current_range);
+            current_range = (*from, *to);
+            furthest = (*from, *to);
+        }
+    }
+    joined.push(current_range);
+    (joined, overlaps)
+}
+
+fn sum_invalid(ids: &[(u128, u128)], base: u32, evaluation: Evaluation) -> Result<u128, String> {
+    sum_invalid_with_rule(ids, Rule::PART_1, base, evaluation)
+}
+
+fn sum_invalid_2(ids: &[(u128, u128)], base: u32, evaluation: Evaluation) -> Result<u128, String> {
+    sum_invalid_with_rule(ids, Rule::PART_2, base, evaluation)
+}
+
+fn sum_invalid_with_rule(
+    ids: &[(u128, u128)],
+    rule: Rule,
+    base: u32,
+    evaluation: Evaluation,
+) -> Result<u128, String> {
+    sum_in_parallel(ids, base, evaluation.threads, |from, to| {
+        if evaluation.scan {
+            sum_by_scan(from, to, rule, base)
+        } else {
+            sum_repeated(from, to, rule, base)
+        }
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Repetitions {
+    Exactly(u32),
+    AtLeast(u32),
+}
+
+// An invalid ID consists of a block of digits that is repeated. The rule decides how often
+// the block needs to be repeated and how long it must be at least.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Rule {
+    repetitions: Repetitions,
+    min_block: u32,
+}
+
+impl Rule {
+    // the block is repeated exactly twice, see `is_invalid`
+    const PART_1: Rule = Rule {
+        repetitions: Repetitions::Exactly(2),
+        min_block: 1,
+    };
+    // the block is repeated at least twice, see `is_invalid_2`
+    const PART_2: Rule = Rule {
+        repetitions: Repetitions::AtLeast(2),
+        min_block: 1,
+    };
+
+    fn new(repetitions: Repetitions, min_block: u32) -> Result<Rule, String> {
+        let (Repetitions::Exactly(k) | Repetitions::AtLeast(k)) = repetitions;
+        if k < 2 {
+            return Err(format!(
+                "a block must be repeated at least twice, not {k} times"
+            ));
+        }
+        if min_block < 1 {
+            return Err("blocks must have at least one digit".to_owned());
+        }
+        Ok(Rule {
+            repetitions,
+            min_block,
+        })
+    }
+
+    fn block_lengths(self, len: u32) -> Vec<u32> {
+        (self.min_block..len)
+            .filter(|e| len.is_multiple_of(*e))
+            .filter(|e| match self.repetitions {
+                Repetitions::Exactly(k) => len / e == k,
+                Repetitions::AtLeast(k) => len / e >= k,
+            })
+            .collect()
+    }
+
+    // checks a single ID, this is what `sum_repeated` and `repeated_ids` do for whole ranges
+    #[cfg(test)]
+    fn matches(self, id: u128, base: u32) -> bool {
+        if id == 0 {
+            return false;
+        }
+        let len = id.ilog(base as u128) + 1;
+        self.block_lengths(len)
+            .into_iter()
+            .filter_map(|e| repunit(len, e, base))
+            .any(|r| id.is_multiple_of(r))
+    }
 }
 
-fn sum_invalid_2(ids: &[(u64, u64)]) -> u64 {
+// All periods an invalid ID of the given length may have, in ascending order. An ID with
+// an allowed block length also has every divisor of the block length as period if its
+// block is periodic itself (e.g. 1111 = 11|11 = 1|1|1|1).
+fn periods(len: u32, rule: Rule) -> Vec<u32> {
+    let block_lengths = rule.block_lengths(len);
+    (1..len)
+        .filter(|d| block_lengths.iter().any(|e| e.is_multiple_of(*d)))
+        .collect()
+}
+
+// Sums up the invalid IDs without looking at every ID in the range. For every length and
+// period, the IDs with that period are a block times a number like 1001001 (in the given
+// base), so their sum is an arithmetic series. IDs with several periods would be counted
+// more than once, so we only count each ID for its smallest period: the sum of IDs with
+// smallest period d is the sum of IDs with period d minus the sums for the smaller
+// periods dividing d. Returns `None` on overflow.
+fn sum_repeated(from: u128, to: u128, rule: Rule, base: u32) -> Option<u128> {
+    let mut sum: u128 = 0;
+    for (len, lo, hi) in lengths(from, to, base) {
+        let mut primitive_sums: Vec<(u32, u128)> = Vec::new();
+        for d in periods(len, rule) {
+            // the sums for the smaller periods are part of the sum with period d,
+            // so this cannot overflow if that one didn't
+            let non_primitive: u128 = primitive_sums
+                .iter()
+                .filter(|(p, _)| d.is_multiple_of(*p))
+                .map(|(_, s)| s)
+                .sum();
+            let with_period = sum_with_period(len, d, lo, hi, base)?;
+            primitive_sums.push((d, with_period - non_primitive));
+        }
+        sum = primitive_sums
+            .iter()
+            .try_fold(sum, |sum, (_, s)| sum.checked_add(*s))?;
+    }
+    Some(sum)
+}
+
+// Lists the invalid IDs in ascending order. Every ID is created from its smallest period,
+// so we do not need to deduplicate anything.
+fn repeated_ids(from: u128, to: u128, rule: Rule, base: u32) -> impl Iterator<Item = u128> {
+    lengths(from, to, base).flat_map(move |(len, lo, hi)| {
+        let mut ids: Vec<u128> = periods(len, rule)
+            .into_iter()
+            .filter_map(|d| Some((d, repunit(len, d, base)?)))
+            .flat_map(|(d, r)| {
+                blocks(len, d, lo, hi, base)
+                    .filter(move |block| !has_smaller_period(*block, d, base))
+                    // blocks are chosen so that the ID is at most `hi`
+                    .map(move |block| block * r)
+            })
+            .collect();
+        ids.sort_unstable();
+        ids
+    })
+}
+
+// splits the range into parts with the same number of digits
+fn lengths(from: u128, to: u128, base: u32) -> impl Iterator<Item = (u32, u128, u128)> {
+    let b = base as u128;
+    let min_len = from.max(1).ilog(b) + 1;
+    let max_len = if to == 0 { 0 } else { to.ilog(b) + 1 };
+    (min_len..=max_len).map(move |len| {
+        // the largest ID with len digits may be too large for u128, `to` isn't
+        let largest = b.checked_pow(len).map_or(u128::MAX, |p| p - 1);
+        (len, from.max(b.pow(len - 1)), to.min(largest))
+    })
+}
+
+// The number that repeats a block of length d to length len, e.g. 10101 for d = 2, len = 6.
+// Returns `None` if that number does not fit into 128 bits.
+fn repunit(len: u32, d: u32, base: u32) -> Option<u128> {
+    let step = (base as u128).checked_pow(d)?;
+    (1..len / d).try_fold(1u128, |r, _| r.checked_mul(step)?.checked_add(1))
+}
+
+// blocks of length d that repeat to an ID between lo and hi
+fn blocks(len: u32, d: u32, lo: u128, hi: u128, base: u32) -> RangeInclusive<u128> {
+    let b = base as u128;
+    match repunit(len, d, base) {
+        // block lengths are at most half of the ID length, so the powers fit
+        Some(r) => lo.div_ceil(r).max(b.pow(d - 1))..=(hi / r).min(b.pow(d) - 1),
+        // repeating even the smallest block would be too large for any ID
+        None => RangeInclusive::new(1, 0),
+    }
+}
+
+// returns `None` on overflow
+fn sum_with_period(len: u32, d: u32, lo: u128, hi: u128, base: u32) -> Option<u128> {
+    let blocks = blocks(len, d, lo, hi, base);
+    if blocks.is_empty() {
+        return Some(0);
+    }
+    let (first, last) = (*blocks.start(), *blocks.end());
+    let count = last - first + 1;
+    // one of count and first + last is even, divide that one before multiplying
+    let block_sum = if count.is_multiple_of(2) {
+        (count / 2).checked_mul(first.checked_add(last)?)?
+    } else {
+        count.checked_mul((first + last) / 2)?
+    };
+    repunit(len, d, base)?.checked_mul(block_sum)
+}
+
+fn has_smaller_period(block: u128, d: u32, base: u32) -> bool {
+    (1..d)
+        .filter(|p| d.is_multiple_of(*p))
+        .any(|p| repunit(d, p, base).is_some_and(|r| block == block % (base as u128).pow(p) * r))
+}
+
+// Checks every single ID, this is way too slow for wide ranges. The repunits are the same
+// for all IDs of a length, so this does not use `Rule::matches`. Returns `None` on overflow.
+fn sum_by_scan(from: u128, to: u128, rule: Rule, base: u32) -> Option<u128> {
+    let mut sum: u128 = 0;
+    for (len, lo, hi) in lengths(from, to, base) {
+        let repunits: Vec<u128> = rule
+            .block_lengths(len)
+            .into_iter()
+            .filter_map(|e| repunit(len, e, base))
+            .collect();
+        for id in (lo..=hi).filter(|id| repunits.iter().any(|r| id.is_multiple_of(*r))) {
+            sum = sum.checked_add(id)?;
+        }
+    }
+    Some(sum)
+}
+
+// checks every single ID, this is way too slow for wide ranges
+#[cfg(test)]
+fn sum_invalid_by_scan(ids: &[(u128, u128)], is_invalid: impl Fn(u128) -> bool) -> u128 {
     ids.iter()
-        .flat_map(|(from, to)| (*from..=*to).filter(|id| is_invalid_2(*id)))
+        .flat_map(|(from, to)| (*from..=*to).filter(|id| is_invalid(*id)))
         .sum()
 }
 
-fn is_invalid(id: u64) -> bool {
+#[cfg(test)]
+fn is_invalid(id: u128, base: u32) -> bool {
     if id == 0 {
         return false;
     }
-    let p = 10u64.pow(id.ilog10().div_ceil(2));
+    let p = (base as u128).pow(id.ilog(base as u128).div_ceil(2));
     id % p == id / p
 }
 
-fn is_invalid_2(id: u64) -> bool {
+#[cfg(test)]
+fn is_invalid_2(id: u128, base: u32) -> bool {
     if id == 0 {
         return false;
     }
-    let len = id.ilog10() + 1;
+    let len = id.ilog(base as u128) + 1;
     let emax = len / 2;
     (1..=emax).filter(|e| len.is_multiple_of(*e)).any(|e| {
-        let p = 10u64.pow(e);
+        let p = (base as u128).pow(e);
         let seq = id % p;
         let mut id = id / p;
         while id != 0 {
@@ -60,49 +469,317 @@ fn is_invalid_2(id: u64) -> bool {
     })
 }
 
-fn parse(input: &str) -> Result<Vec<(u64, u64)>, String> {
-    input.trim().split(',').map(parse_line).collect()
+fn parse(input: &str, base: u32) -> Result<Vec<(u128, u128)>, String> {
+    input
+        .trim()
+        .split(',')
+        .map(|line| parse_line(line, base))
+        .collect()
 }
 
-fn parse_line(line: &str) -> Result<(u64, u64), String> {
+fn parse_line(line: &str, base: u32) -> Result<(u128, u128), String> {
     let (from, to) = line
         .split_once('-')
         .ok_or_else(|| format!("line '{line}' does not contain a dash"))?;
-    Ok((
-        from.parse::<u64>()
-            .map_err(|e| format!("unable to parse ID '{from}': {e}"))?,
-        to.parse::<u64>()
-            .map_err(|e| format!("unable to parse ID '{to}': {e}"))?,
-    ))
+    let from = u128::from_str_radix(from, base)
+        .map_err(|e| format!("unable to parse ID '{from}': {e}"))?;
+    let to =
+        u128::from_str_radix(to, base).map_err(|e| format!("unable to parse ID '{to}': {e}"))?;
+    if from > to {
+        return Err(format!("range '{line}' ends before it starts"));
+    }
+    Ok((from, to))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    const SEQUENTIAL: Evaluation = Evaluation {
+        scan: false,
+        threads: 1,
+    };
+
     static EXAMPLE_INPUT: &str = "11-22,95-115,998-1012,1188511880-1188511890,222220-222224,1698522-1698528,446443-446449,38593856-38593862,565653-565659,824824821-824824827,2121212118-2121212124";
 
     #[test]
     fn sum_invalid_works_for_example() {
         // given
-        let ids = parse(EXAMPLE_INPUT).expect("expected valid input");
+        let ids = parse(EXAMPLE_INPUT, 10).expect("expected valid input");
 
         // when
-        let sum = sum_invalid(&ids);
+        let sum = sum_invalid(&ids, 10, SEQUENTIAL);
 
         // then
-        assert_eq!(sum, 1227775554);
+        assert_eq!(sum, Ok(1227775554));
     }
 
     #[test]
     fn sum_invalid_2_works_for_example() {
         // given
-        let ids = parse(EXAMPLE_INPUT).expect("expected valid input");
+        let ids = parse(EXAMPLE_INPUT, 10).expect("expected valid input");
+
+        // when
+        let sum = sum_invalid_2(&ids, 10, SEQUENTIAL);
+
+        // then
+        assert_eq!(sum, Ok(4174379265));
+    }
+
+    #[test]
+    fn sums_agree_with_checking_every_id() {
+        // given
+        let ids = parse(
+            "0-12000,99990-1010101,22222222-22233333,999999990-1000000010",
+            10,
+        )
+        .expect("expected valid input");
+
+        // when
+        let sum = sum_invalid(&ids, 10, SEQUENTIAL);
+        let sum_2 = sum_invalid_2(&ids, 10, SEQUENTIAL);
+
+        // then
+        assert_eq!(sum, Ok(sum_invalid_by_scan(&ids, |id| is_invalid(id, 10))));
+        assert_eq!(
+            sum_2,
+            Ok(sum_invalid_by_scan(&ids, |id| is_invalid_2(id, 10)))
+        );
+    }
+
+    #[test]
+    fn repeated_ids_lists_every_invalid_id_once() {
+        // given
+        let (from, to) = (1, 1_000_000);
+
+        // when
+        let ids: Vec<u128> = repeated_ids(from, to, Rule::PART_2, 10).collect();
+
+        // then
+        let expected: Vec<u128> = (from..=to).filter(|id| is_invalid_2(*id, 10)).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn sum_invalid_2_agrees_with_listing_for_huge_ranges() {
+        // given
+        let ids = &[(1, 9_999_999_999)];
+
+        // when
+        let sum = sum_invalid_2(ids, 10, SEQUENTIAL);
+
+        // then
+        let expected: u128 = repeated_ids(1, 9_999_999_999, Rule::PART_2, 10).sum();
+        assert_eq!(sum, Ok(expected));
+    }
+
+    #[test]
+    fn sums_work_for_other_bases() {
+        for base in [2, 3, 7, 16, 36] {
+            // given
+            let input = format!(
+                "1-{},{}-{}",
+                format_radix(5000, base),
+                format_radix(70000, base),
+                format_radix(90000, base)
+            );
+            let ids = parse(&input, base).expect("expected valid input");
+
+            // when
+            let sum = sum_invalid(&ids, base, SEQUENTIAL);
+            let sum_2 = sum_invalid_2(&ids, base, SEQUENTIAL);
+
+            // then
+            assert_eq!(
+                sum,
+                Ok(sum_invalid_by_scan(&ids, |id| is_invalid(id, base)))
+            );
+            assert_eq!(
+                sum_2,
+                Ok(sum_invalid_by_scan(&ids, |id| is_invalid_2(id, base)))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reads_hexadecimal_ids() {
+        // given
+        let input = "ff-101,abab-abac";
+
+        // when
+        let ids = parse(input, 16).expect("expected valid input");
+
+        // then
+        assert_eq!(ids, vec![(0xff, 0x101), (0xabab, 0xabac)]);
+        assert_eq!(sum_invalid(&ids, 16, SEQUENTIAL), Ok(0xff + 0xabab));
+    }
+
+    #[test]
+    fn join_ranges_counts_overlapping_ids_once() {
+        // given
+        let ids =
+            parse("95-115,11-22,100-120,121-130,15-20,11-22", 10).expect("expected valid input");
+
+        // when
+        let (joined, overlaps) = join_ranges(ids);
+
+        // then
+        assert_eq!(joined, vec![(11, 22), (95, 130)]);
+        assert_eq!(
+            overlaps,
+            vec![
+                ((11, 22), (11, 22)),
+                ((11, 22), (15, 20)),
+                ((95, 115), (100, 120))
+            ]
+        );
+        assert_eq!(sum_invalid(&joined, 10, SEQUENTIAL), Ok(11 + 22 + 99));
+        assert_eq!(
+            sum_invalid_2(&joined, 10, SEQUENTIAL),
+            Ok(11 + 22 + 99 + 111)
+        );
+    }
+
+    #[test]
+    fn join_ranges_reports_overlaps_with_input_ranges() {
+        // given
+        let ids = parse("11-22,20-30,25-27,31-40,35-36", 10).expect("expected valid input");
 
         // when
-        let sum = sum_invalid_2(&ids);
+        let (joined, overlaps) = join_ranges(ids);
 
         // then
-        assert_eq!(sum, 4174379265);
+        assert_eq!(joined, vec![(11, 40)]);
+        assert_eq!(
+            overlaps,
+            vec![
+                ((11, 22), (20, 30)),
+                ((20, 30), (25, 27)),
+                ((31, 40), (35, 36))
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_inverted_ranges() {
+        // given
+        let input = "11-22,115-95";
+
+        // when
+        let result = parse(input, 10);
+
+        // then
+        assert_eq!(
+            result,
+            Err("range '115-95' ends before it starts".to_owned())
+        );
+    }
+
+    #[test]
+    fn rule_presets_match_puzzle_checks() {
+        for base in [2, 10, 16] {
+            for id in 0..20_000 {
+                assert_eq!(Rule::PART_1.matches(id, base), is_invalid(id, base));
+                assert_eq!(Rule::PART_2.matches(id, base), is_invalid_2(id, base));
+            }
+        }
+    }
+
+    #[test]
+    fn sums_work_for_custom_rules() {
+        // given
+        let rules = [
+            Rule::new(Repetitions::Exactly(3), 1),
+            Rule::new(Repetitions::AtLeast(3), 1),
+            Rule::new(Repetitions::AtLeast(2), 2),
+            Rule::new(Repetitions::Exactly(2), 3),
+        ];
+
+        for rule in rules {
+            let rule = rule.expect("expected valid rule");
+
+            // when
+            let sum = sum_repeated(1, 1_100_000, rule, 10);
+
+            // then
+            let expected: u128 = (1..=1_100_000).filter(|id| rule.matches(*id, 10)).sum();
+            assert_eq!(sum, Some(expected), "sum differs for {rule:?}");
+        }
+    }
+
+    #[test]
+    fn exactly_three_repetitions_include_repeated_blocks() {
+        // given
+        let rule = Rule::new(Repetitions::Exactly(3), 1).expect("expected valid rule");
+
+        // when
+        let ids: Vec<u128> = repeated_ids(111_000, 122_000, rule, 10).collect();
+
+        // then
+        // 111111 is 11 three times (and 1 six times), 121212 is 12 three times
+        assert_eq!(ids, vec![111_111, 121_212]);
+    }
+
+    #[test]
+    fn sums_work_beyond_64_bits() {
+        // given
+        let block = 123_456_789_012_345u128;
+        let id = block * 1_000_000_000_000_001;
+        let input = format!("{}-{}", id - 5, id + 5);
+        let ids = parse(&input, 10).expect("expected valid input");
+
+        // when
+        let sum = sum_invalid(&ids, 10, SEQUENTIAL);
+        let sum_2 = sum_invalid_2(&ids, 10, SEQUENTIAL);
+
+        // then
+        assert!(id > u64::MAX as u128);
+        assert_eq!(sum, Ok(id));
+        assert_eq!(sum_2, Ok(id));
+    }
+
+    #[test]
+    fn sums_report_overflow() {
+        // given
+        // the largest 38 digit IDs repeat 19 digit blocks, a few of them are too much
+        let ids = parse(&format!("1-{}", u128::MAX), 10).expect("expected valid input");
+        let hex_ids = parse(&format!("5-6,10-{:x}", u128::MAX), 16).expect("expected valid input");
+        let evaluation = Evaluation {
+            scan: false,
+            threads: 4,
+        };
+
+        // when
+        let sum = sum_invalid(&ids, 10, SEQUENTIAL);
+        let hex_sum = sum_invalid(&hex_ids, 16, evaluation);
+
+        // then
+        assert!(sum.is_err());
+        // the range is the one from the input, not one of the chunks it is split into
+        assert_eq!(
+            hex_sum,
+            Err(format!(
+                "the sum of invalid IDs in 10-{:x} does not fit into 128 bits",
+                u128::MAX
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_args_limits_threads() {
+        for (threads, valid) in [("0", false), ("1", true), ("1024", true), ("50000", false)] {
+            // given
+            let args = ["--threads", threads, "input.txt"].map(str::to_owned);
+
+            // when
+            let options = parse_args(args.into_iter());
+
+            // then
+            assert_eq!(
+                options.is_ok(),
+                valid,
+                "unexpected result for {threads} threads"
+            );
+        }
     }
 }
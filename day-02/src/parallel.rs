@@ -0,0 +1,190 @@
+use super::{IdRange, format_radix};
+use std::panic::resume_unwind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// more chunks than threads, so a thread that got easy chunks can help out with the rest
+const CHUNKS_PER_THREAD: usize = 8;
+
+// Splits the ranges into chunks, sums up every chunk with `sum_range` on `threads` threads
+// and adds up the results in the order of the chunks. The result does not depend on which
+// thread got which chunk. `sum_range` returns `None` on overflow, which is reported for the
+// whole range the chunk belongs to.
+pub fn sum_in_parallel<F>(
+    ids: &[IdRange],
+    base: u32,
+    threads: usize,
+    sum_range: F,
+) -> Result<u128, String>
+where
+    F: Fn(u128, u128) -> Option<u128> + Sync,
+{
+    let chunk_count = threads
+        .checked_mul(CHUNKS_PER_THREAD)
+        .ok_or_else(|| format!("{threads} threads are too many"))?;
+    let chunks = split_ranges(ids, chunk_count);
+    // there is no point in having threads without chunks
+    let threads = threads.min(chunks.len());
+    let next_chunk = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Option<u128>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                thread::Builder::new().spawn_scoped(scope, || {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next_chunk.fetch_add(1, Ordering::Relaxed);
+                        let Some((_, (from, to))) = chunks.get(i) else {
+                            return results;
+                        };
+                        results.push((i, sum_range(*from, *to)));
+                    }
+                })
+            })
+            .collect();
+        // the threads that did start take all chunks, but we still report that not all could
+        let mut results = Vec::new();
+        let mut spawn_error: Option<String> = None;
+        for worker in workers {
+            match worker {
+                Ok(worker) => results.extend(worker.join().unwrap_or_else(|e| resume_unwind(e))),
+                Err(e) => spawn_error = Some(format!("unable to start a thread: {e}")),
+            }
+        }
+        match spawn_error {
+            Some(e) => Err(e),
+            None => Ok(results),
+        }
+    })?;
+    results.sort_unstable_by_key(|(i, _)| *i);
+    let mut range_sums: Vec<Option<u128>> = vec![Some(0); ids.len()];
+    for (i, chunk_sum) in results {
+        let range = chunks[i].0;
+        range_sums[range] = range_sums[range]
+            .zip(chunk_sum)
+            .and_then(|(a, b)| a.checked_add(b));
+    }
+    range_sums
+        .into_iter()
+        .zip(ids)
+        .try_fold(0u128, |sum, (range_sum, (from, to))| {
+            let range_sum = range_sum.ok_or_else(|| {
+                format!(
+                    "the sum of invalid IDs in {}-{} does not fit into 128 bits",
+                    format_radix(*from, base),
+                    format_radix(*to, base)
+                )
+            })?;
+            sum.checked_add(range_sum)
+                .ok_or_else(|| "the sum of invalid IDs does not fit into 128 bits".to_owned())
+        })
+}
+
+// Splits the ranges into about `count` chunks of the same width, narrow ranges stay in one
+// piece. Every chunk comes with the index of its range.
+fn split_ranges(ids: &[IdRange], count: usize) -> Vec<(usize, IdRange)> {
+    let total_width = ids
+        .iter()
+        .map(|(from, to)| to - from)
+        .fold(0u128, u128::saturating_add);
+    let width = (total_width / count.max(1) as u128).max(1);
+    let mut chunks: Vec<(usize, IdRange)> = Vec::with_capacity(count + ids.len());
+    for (range, (from, to)) in ids.iter().enumerate() {
+        let mut start = *from;
+        loop {
+            let end = start.saturating_add(width - 1).min(*to);
+            chunks.push((range, (start, end)));
+            if end == *to {
+                break;
+            }
+            start = end + 1;
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Rule, repeated_ids, sum_by_scan, sum_repeated};
+    use std::time::Instant;
+
+    #[test]
+    fn split_ranges_covers_every_id_once() {
+        // given
+        let ids = &[(0, 99), (150, 152), (1000, 1000), (2000, u128::MAX)];
+
+        // when
+        let chunks = split_ranges(ids, 10);
+
+        // then
+        assert_eq!(chunks[0], (0, (0, 99)));
+        assert_eq!(chunks[1], (1, (150, 152)));
+        assert_eq!(chunks[2], (2, (1000, 1000)));
+        assert_eq!(chunks[3].1.0, 2000);
+        assert_eq!(
+            chunks.last().map(|(range, (_, to))| (*range, *to)),
+            Some((3, u128::MAX))
+        );
+        assert!(chunks.iter().skip(3).all(|(range, _)| *range == 3));
+        assert!(chunks.windows(2).skip(3).all(|w| w[0].1.1 + 1 == w[1].1.0));
+        assert!(chunks.len() <= 14);
+    }
+
+    #[test]
+    fn sum_in_parallel_rejects_too_many_threads() {
+        // given
+        let ids = &[(1, 100)];
+
+        // when
+        let sum = sum_in_parallel(ids, 10, usize::MAX, |from, to| {
+            sum_repeated(from, to, Rule::PART_2, 10)
+        });
+
+        // then
+        assert_eq!(sum, Err(format!("{} threads are too many", usize::MAX)));
+    }
+
+    #[test]
+    fn sum_in_parallel_does_not_depend_on_thread_count() {
+        // given
+        let ids = &[(1, 2_000_000), (10_000_000_000, 10_000_500_000)];
+
+        // when
+        let sums: Vec<Result<u128, String>> = [1, 2, 3, 8]
+            .into_iter()
+            .map(|threads| {
+                sum_in_parallel(ids, 10, threads, |from, to| {
+                    sum_by_scan(from, to, Rule::PART_2, 10)
+                })
+            })
+            .collect();
+
+        // then
+        let expected: u128 = ids
+            .iter()
+            .flat_map(|(from, to)| repeated_ids(*from, *to, Rule::PART_2, 10))
+            .sum();
+        assert!(sums.iter().all(|sum| *sum == Ok(expected)), "{sums:?}");
+    }
+
+    // cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_scan_in_parallel() {
+        let ids = &[(1, 3_000_000_000)];
+        let expected = sum_repeated(1, 3_000_000_000, Rule::PART_2, 10);
+        let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut thread_counts = vec![1, 2, 4, max_threads];
+        thread_counts.sort_unstable();
+        thread_counts.dedup();
+
+        for threads in thread_counts {
+            let start = Instant::now();
+            let sum = sum_in_parallel(ids, 10, threads, |from, to| {
+                sum_by_scan(from, to, Rule::PART_2, 10)
+            });
+            println!("{threads} thread(s): {:?}", start.elapsed());
+            assert_eq!(sum.ok(), expected);
+        }
+    }
+}
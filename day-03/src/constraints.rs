@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+// Wiring constraints for the banks, read from a file like
+//
+// # comments and blank lines are ignored
+// min-gap: 1
+// bank 3: 0, 4, 5
+//
+// Banks are counted from 1 like in the output of `--show`, the forbidden positions in a bank
+// from 0. The minimum gap is the number of batteries that have to be left out between two
+// chosen ones and applies to all banks.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Constraints {
+    pub min_gap: usize,
+    // index of the bank (from 0) to the positions of its dead cells
+    forbidden: HashMap<usize, HashSet<usize>>,
+    // line number, bank index and positions of every bank line, to check them against the
+    // banks once we know them
+    bank_lines: Vec<(usize, usize, Vec<usize>)>,
+}
+
+impl Constraints {
+    pub fn forbidden(&self, bank: usize) -> Option<&HashSet<usize>> {
+        self.forbidden.get(&bank)
+    }
+
+    // a typo in the constraints must not silently leave a bank unconstrained
+    pub fn check(&self, banks: &[Vec<u64>]) -> Result<(), String> {
+        for (line_no, bank, positions) in &self.bank_lines {
+            let Some(batteries) = banks.get(*bank) else {
+                return Err(format!(
+                    "line {line_no}: there is no bank {}, there are only {} banks",
+                    bank + 1,
+                    banks.len()
+                ));
+            };
+            if let Some(position) = positions.iter().find(|p| **p >= batteries.len()) {
+                return Err(format!(
+                    "line {line_no}: bank {} has no position {position}, it only has {} batteries",
+                    bank + 1,
+                    batteries.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_unconstrained(&self, bank: usize) -> bool {
+        self.min_gap == 0
+            && self
+                .forbidden(bank)
+                .is_none_or(|positions| positions.is_empty())
+    }
+}
+
+pub fn parse_constraints(input: &str) -> Result<Constraints, String> {
+    let mut constraints = Constraints::default();
+    for (line_no, line) in input.lines().enumerate() {
+        parse_constraint_line(line_no + 1, line, &mut constraints)
+            .map_err(|e| format!("line {}: {e}", line_no + 1))?;
+    }
+    Ok(constraints)
+}
+
+fn parse_constraint_line(
+    line_no: usize,
+    line: &str,
+    constraints: &mut Constraints,
+) -> Result<(), String> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+    let (key, value) = line
+        .split_once(':')
+        .ok_or_else(|| format!("expected 'min-gap: N' or 'bank N: positions', got '{line}'"))?;
+    let key = key.trim();
+    if key == "min-gap" {
+        constraints.min_gap = parse_number(value)?;
+    } else if let Some(bank) = key.strip_prefix("bank ") {
+        let bank: usize = parse_number(bank)?;
+        if bank == 0 {
+            return Err("banks are counted from 1".to_owned());
+        }
+        let positions: Vec<usize> = value
+            .split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(parse_number)
+            .collect::<Result<_, _>>()?;
+        constraints
+            .forbidden
+            .entry(bank - 1)
+            .or_default()
+            .extend(positions.iter().copied());
+        constraints.bank_lines.push((line_no, bank - 1, positions));
+    } else {
+        return Err(format!("unknown constraint '{key}'"));
+    }
+    Ok(())
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.trim()
+        .parse()
+        .map_err(|e| format!("invalid number '{}': {e}", s.trim()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_constraints_reads_gap_and_forbidden_positions() {
+        // given
+        let input = "# dead cells\nmin-gap: 1\n\nbank 3: 0, 4,5\nbank 1: 2 # broken\nbank 3: 7\n";
+
+        // when
+        let constraints = parse_constraints(input).expect("expected valid constraints");
+
+        // then
+        assert_eq!(constraints.min_gap, 1);
+        assert_eq!(constraints.forbidden(2), Some(&HashSet::from([0, 4, 5, 7])));
+        assert_eq!(constraints.forbidden(0), Some(&HashSet::from([2])));
+        assert_eq!(constraints.forbidden(1), None);
+        assert_eq!(
+            parse_constraints("min-gap: 1\nbank 0: 1"),
+            Err("line 2: banks are counted from 1".to_owned())
+        );
+    }
+
+    #[test]
+    fn check_reports_lines_that_do_not_fit_the_banks() {
+        // given
+        let banks = vec![vec![1, 2, 3], vec![4, 5]];
+        let valid =
+            parse_constraints("bank 1: 0, 2\nbank 2: 1").expect("expected valid constraints");
+        let no_bank =
+            parse_constraints("bank 1: 0\n\nbank 3: 1").expect("expected valid constraints");
+        let no_position =
+            parse_constraints("bank 2: 0,2 # typo").expect("expected valid constraints");
+
+        // when
+        let results = [valid, no_bank, no_position].map(|c| c.check(&banks));
+
+        // then
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Err("line 3: there is no bank 3, there are only 2 banks".to_owned()),
+                Err("line 1: bank 2 has no position 2, it only has 2 batteries".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::ops::AddAssign;
+
+// A non-negative number with as many decimal digits as needed. Joltages are just digits
+// picked from a bank, so there is no point in converting them to binary.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Decimal {
+    // least significant digit first, without leading zeros, so zero has no digits at all
+    digits: Vec<u8>,
+}
+
+impl Decimal {
+    // the digits come most significant first, like in a bank
+    pub fn from_digits(digits: impl DoubleEndedIterator<Item = u64>) -> Decimal {
+        let mut digits: Vec<u8> = digits.rev().map(|digit| digit as u8).collect();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Decimal { digits }
+    }
+}
+
+impl AddAssign<&Decimal> for Decimal {
+    fn add_assign(&mut self, other: &Decimal) {
+        if self.digits.len() < other.digits.len() {
+            self.digits.resize(other.digits.len(), 0);
+        }
+        let mut carry = 0;
+        for (i, digit) in self.digits.iter_mut().enumerate() {
+            if carry == 0 && i >= other.digits.len() {
+                return;
+            }
+            let sum = *digit + other.digits.get(i).copied().unwrap_or(0) + carry;
+            *digit = sum % 10;
+            carry = sum / 10;
+        }
+        if carry > 0 {
+            self.digits.push(carry);
+        }
+    }
+}
+
+impl Sum for Decimal {
+    fn sum<I: Iterator<Item = Decimal>>(iter: I) -> Decimal {
+        iter.fold(Decimal::default(), |mut sum, n| {
+            sum += &n;
+            sum
+        })
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        let s: String = self
+            .digits
+            .iter()
+            .rev()
+            .map(|digit| char::from(b'0' + digit))
+            .collect();
+        write!(f, "{s}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sum_carries_over_many_digits() {
+        // given
+        let numbers = [
+            Decimal::from_digits([9; 30].into_iter()),
+            Decimal::from_digits([0, 0, 1].into_iter()),
+            Decimal::from_digits([0].into_iter()),
+        ];
+
+        // when
+        let sum: Decimal = numbers.iter().cloned().sum();
+
+        // then
+        assert_eq!(numbers[1].to_string(), "1");
+        assert_eq!(numbers[2].to_string(), "0");
+        assert_eq!(sum.to_string(), format!("1{}", "0".repeat(30)));
+    }
+}
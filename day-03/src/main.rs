@@ -1,32 +1,282 @@
 #![forbid(unsafe_code)]
 
+use std::collections::HashSet;
 use std::env;
+use std::fmt::Display;
 use std::fs::read_to_string;
 use std::path::Path;
+use std::str::FromStr;
+
+mod constraints;
+mod decimal;
+#[cfg(test)]
+mod rng;
+mod selection;
+
+use constraints::{Constraints, parse_constraints};
+use decimal::Decimal;
+use selection::{Order, Selector};
 
 fn main() -> Result<(), String> {
-    let filename = env::args()
-        .nth(1)
-        .ok_or_else(|| "No file name given.".to_owned())?;
-    let content = read_to_string(Path::new(&filename)).map_err(|e| e.to_string())?;
+    let options = parse_args(env::args().skip(1))?;
+    let content = read_to_string(Path::new(&options.filename)).map_err(|e| e.to_string())?;
     let banks = parse(&content)?;
+    let constraints = match &options.constraints {
+        Some(filename) => {
+            parse_constraints(&read_to_string(Path::new(filename)).map_err(|e| e.to_string())?)?
+        }
+        None => Constraints::default(),
+    };
+    constraints.check(&banks)?;
 
-    let joltage = max_joltage_sum(&banks, 2);
-    println!("The total output of joltage is {joltage}.");
+    let order = options.order;
+    if options.show {
+        let n = options.digits.unwrap_or(12);
+        print!("{}", format_selections(&banks, n, &constraints, order));
+        return Ok(());
+    }
 
-    let joltage = max_joltage_sum(&banks, 12);
-    println!("The total output of high joltage is {joltage}.");
+    if let Some(k) = options.top {
+        let n = options.digits.unwrap_or(12);
+        print!("{}", format_top(&banks, n, k, &constraints, order));
+        return Ok(());
+    }
+
+    let which = match order {
+        Order::Largest => "",
+        Order::Smallest => "lowest ",
+    };
+    if let Some(n) = options.digits {
+        let joltage = joltage_sum(&banks, n, &constraints, order);
+        println!("The {which}total output of joltage for {n} batteries is {joltage}.");
+        return Ok(());
+    }
+
+    let joltage = joltage_sum(&banks, 2, &constraints, order);
+    println!("The {which}total output of joltage is {joltage}.");
+
+    let joltage = joltage_sum(&banks, 12, &constraints, order);
+    println!("The {which}total output of high joltage is {joltage}.");
 
     Ok(())
 }
 
-fn max_joltage_sum(banks: &[Vec<u64>], n: u8) -> u64 {
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Options {
+    filename: String,
+    // print the chosen batteries of every bank
+    show: bool,
+    // file with dead cells and the minimum gap between chosen batteries
+    constraints: Option<String>,
+    // whether we want the largest or the smallest values
+    order: Order,
+    // print the k best distinct values of every bank instead of the sums
+    top: Option<usize>,
+    // number of batteries for `show`, `top` and the sum, instead of the ones from the puzzle
+    digits: Option<usize>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut filename: Option<String> = None;
+    let mut show = false;
+    let mut constraints: Option<String> = None;
+    let mut order = Order::Largest;
+    let mut top: Option<usize> = None;
+    let mut digits: Option<usize> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--show" => show = true,
+            "--constraints" => constraints = Some(parse_option_value(&arg, args.next())?),
+            "--min" => order = Order::Smallest,
+            "--top" => top = Some(parse_option_value(&arg, args.next())?),
+            "--digits" => digits = Some(parse_option_value(&arg, args.next())?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            _ => {
+                if filename.is_some() {
+                    return Err(format!("unexpected extra argument '{arg}'"));
+                }
+                filename = Some(arg);
+            }
+        }
+    }
+
+    if show && top.is_some() {
+        return Err("'--show' and '--top' cannot be combined".to_owned());
+    }
+    if top == Some(0) {
+        return Err("'--top' needs at least one value".to_owned());
+    }
+    if digits == Some(0) {
+        return Err("'--digits' needs at least one battery".to_owned());
+    }
+    Ok(Options {
+        filename: filename.ok_or_else(|| "No file name given.".to_owned())?,
+        show,
+        constraints,
+        order,
+        top,
+        digits,
+    })
+}
+
+fn parse_option_value<T>(option: &str, value: Option<String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| format!("option '{option}' needs a value"))?;
+    value
+        .parse()
+        .map_err(|e| format!("invalid value '{value}' for option '{option}': {e}"))
+}
+
+// Writes every bank with a line below that marks the chosen batteries with '^' and dead
+// cells with 'x', e.g.
+//
+// bank 1: 97 (positions 0, 2)
+// 987654321111111
+// ^x^
+fn format_selections(
+    banks: &[Vec<u64>],
+    n: usize,
+    constraints: &Constraints,
+    order: Order,
+) -> String {
+    let mut out = String::new();
+    for (i, bank) in banks.iter().enumerate() {
+        let positions = select(bank, n, i, constraints, order);
+        if positions.is_empty() {
+            out.push_str(&format!("bank {}: cannot switch on {n} batteries\n", i + 1));
+        } else {
+            let positions_list: Vec<String> = positions.iter().map(|p| p.to_string()).collect();
+            out.push_str(&format!(
+                "bank {}: {} (positions {})\n",
+                i + 1,
+                joltage(bank, &positions),
+                positions_list.join(", ")
+            ));
+        }
+        let digits: String = bank.iter().map(|digit| digit.to_string()).collect();
+        let forbidden: Vec<usize> = constraints
+            .forbidden(i)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        let marks_len = positions
+            .iter()
+            .chain(&forbidden)
+            .max()
+            .map_or(0, |p| p + 1);
+        let mut marks = vec![' '; marks_len];
+        for p in &forbidden {
+            marks[*p] = 'x';
+        }
+        for p in &positions {
+            marks[*p] = '^';
+        }
+        out.push_str(&digits);
+        out.push('\n');
+        out.extend(marks);
+        out.push('\n');
+    }
+    out
+}
+
+// Writes the k best distinct values of every bank, e.g.
+//
+// bank 1: 98, 97, 96
+fn format_top(
+    banks: &[Vec<u64>],
+    n: usize,
+    k: usize,
+    constraints: &Constraints,
+    order: Order,
+) -> String {
+    let mut out = String::new();
+    for (i, bank) in banks.iter().enumerate() {
+        let values: Vec<String> = selector(bank, n, i, constraints)
+            .top(k, order)
+            .iter()
+            .map(|positions| joltage(bank, positions).to_string())
+            .collect();
+        if values.is_empty() {
+            out.push_str(&format!("bank {}: cannot switch on {n} batteries\n", i + 1));
+        } else {
+            out.push_str(&format!("bank {}: {}\n", i + 1, values.join(", ")));
+        }
+    }
+    out
+}
+
+fn joltage_sum(banks: &[Vec<u64>], n: usize, constraints: &Constraints, order: Order) -> Decimal {
     banks
         .iter()
-        .map(|bank| max_joltage_for_bank(bank, n, 0))
+        .enumerate()
+        .map(|(i, bank)| joltage(bank, &select(bank, n, i, constraints, order)))
         .sum()
 }
 
+// chooses the batteries of the bank with the given index (from 0)
+fn select(
+    bank: &[u64],
+    n: usize,
+    bank_index: usize,
+    constraints: &Constraints,
+    order: Order,
+) -> Vec<usize> {
+    if order == Order::Largest && constraints.is_unconstrained(bank_index) {
+        select_max(bank, n)
+    } else {
+        selector(bank, n, bank_index, constraints).best(order)
+    }
+}
+
+fn selector<'a>(
+    bank: &'a [u64],
+    n: usize,
+    bank_index: usize,
+    constraints: &Constraints,
+) -> Selector<'a> {
+    Selector::new(
+        bank,
+        n,
+        constraints.min_gap,
+        constraints.forbidden(bank_index).unwrap_or(&HashSet::new()),
+    )
+}
+
+fn joltage(bank: &[u64], positions: &[usize]) -> Decimal {
+    Decimal::from_digits(positions.iter().map(|p| bank[*p]))
+}
+
+// Picks the positions of the largest subsequence of n digits in one pass: a digit pushes
+// smaller digits before it off the stack as long as enough digits are left to fill up the
+// rest. Equal digits stay on the stack, so of several equal choices we get the earliest
+// batteries. Returns no positions at all if the bank has fewer than n batteries.
+fn select_max(bank: &[u64], n: usize) -> Vec<usize> {
+    if bank.len() < n {
+        return vec![];
+    }
+    let mut stack: Vec<usize> = Vec::with_capacity(n);
+    for (i, digit) in bank.iter().enumerate() {
+        let remaining = bank.len() - i;
+        while stack
+            .last()
+            .is_some_and(|top| bank[*top] < *digit && stack.len() - 1 + remaining >= n)
+        {
+            stack.pop();
+        }
+        if stack.len() < n {
+            stack.push(i);
+        }
+    }
+    stack
+}
+
+// the original solution, looks at a window of the bank for every digit
+#[cfg(test)]
 fn max_joltage_for_bank(bank: &[u64], n: u8, current: u64) -> u64 {
     if bank.len() < n.into() {
         0
@@ -64,4 +314,163 @@ fn parse_bank(line: &str) -> Result<Vec<u64>, String> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rng::Rng;
+
+    static EXAMPLE_INPUT: &str = "987654321111111
+811111111111119
+234234234234278
+818181911112111
+";
+
+    #[test]
+    fn joltage_sum_works_for_example() {
+        // given
+        let banks = parse(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let joltage = joltage_sum(&banks, 2, &Constraints::default(), Order::Largest);
+        let high_joltage = joltage_sum(&banks, 12, &Constraints::default(), Order::Largest);
+
+        // then
+        assert_eq!(joltage.to_string(), "357");
+        assert_eq!(high_joltage.to_string(), "3121910778619");
+    }
+
+    #[test]
+    fn select_max_returns_positions_of_chosen_batteries() {
+        // given
+        let banks = parse(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let positions: Vec<Vec<usize>> = banks.iter().map(|bank| select_max(bank, 12)).collect();
+
+        // then
+        assert_eq!(positions[0], vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(positions[1], vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 14]);
+        assert_eq!(positions[2], vec![2, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert_eq!(positions[3], vec![0, 2, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert!(select_max(&banks[0], 16).is_empty());
+    }
+
+    #[test]
+    fn format_selections_marks_chosen_batteries() {
+        // given
+        let banks = parse("811111111111119\n9\n").expect("expected valid input");
+
+        // when
+        let out = format_selections(&banks, 2, &Constraints::default(), Order::Largest);
+
+        // then
+        assert_eq!(
+            out,
+            "bank 1: 89 (positions 0, 14)\n811111111111119\n^             ^\nbank 2: cannot switch on 2 batteries\n9\n\n"
+        );
+    }
+
+    #[test]
+    fn max_joltage_agrees_with_recursive_solution() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..5000 {
+            // given
+            let len = (rng.next() % 30) as usize;
+            // few different digits, so there are many ties
+            let digit_count = rng.next() % 10 + 1;
+            let bank: Vec<u64> = (0..len).map(|_| rng.next() % digit_count).collect();
+            // 19 digits still fit into a u64
+            let n = (rng.next() % 19) as usize + 1;
+
+            // when
+            let positions = select_max(&bank, n);
+            let joltage = joltage(&bank, &positions);
+
+            // then
+            assert_eq!(
+                joltage.to_string(),
+                max_joltage_for_bank(&bank, n as u8, 0).to_string(),
+                "joltage differs for bank {bank:?} and n = {n}"
+            );
+            assert!(positions.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(
+                Selector::new(&bank, n, 0, &HashSet::new()).best(Order::Largest),
+                positions
+            );
+        }
+    }
+
+    #[test]
+    fn joltage_sum_works_beyond_64_bits() {
+        // given
+        let banks = parse(&format!("{}\n{}\n", "9".repeat(50), "1".repeat(40)))
+            .expect("expected valid input");
+
+        // when
+        let joltage = joltage_sum(&banks, 40, &Constraints::default(), Order::Largest);
+
+        // then
+        // 99...9 + 11...1 = 111...10
+        assert_eq!(joltage.to_string(), "1".repeat(40) + "0");
+    }
+
+    #[test]
+    fn format_selections_marks_dead_cells() {
+        // given
+        let banks = parse("987654321111111\n").expect("expected valid input");
+        let constraints =
+            parse_constraints("min-gap: 1\nbank 1: 1").expect("expected valid constraints");
+
+        // when
+        let out = format_selections(&banks, 3, &constraints, Order::Largest);
+        let joltage = joltage_sum(&banks, 3, &constraints, Order::Largest);
+
+        // then
+        assert_eq!(
+            out,
+            "bank 1: 975 (positions 0, 2, 4)\n987654321111111\n^x^ ^\n"
+        );
+        assert_eq!(joltage.to_string(), "975");
+    }
+
+    #[test]
+    fn parse_args_uses_digits_for_show_and_top() {
+        // given
+        let show = ["--show", "--digits", "2", "input.txt"].map(str::to_owned);
+        let top = ["--top", "3", "input.txt"].map(str::to_owned);
+        let both = ["--show", "--top", "3", "input.txt"].map(str::to_owned);
+        let no_values = ["--top", "0", "input.txt"].map(str::to_owned);
+        let no_digits = ["--show", "--digits", "0", "input.txt"].map(str::to_owned);
+
+        // when
+        let show = parse_args(show.into_iter()).expect("expected valid arguments");
+        let top = parse_args(top.into_iter()).expect("expected valid arguments");
+        let both = parse_args(both.into_iter());
+        let no_values = parse_args(no_values.into_iter());
+        let no_digits = parse_args(no_digits.into_iter());
+
+        // then
+        assert!(show.show);
+        assert_eq!(show.digits, Some(2));
+        assert_eq!(top.top, Some(3));
+        assert_eq!(top.digits, None);
+        assert!(both.is_err());
+        assert!(no_values.is_err());
+        assert!(no_digits.is_err());
+    }
+
+    #[test]
+    fn queries_work_for_example() {
+        // given
+        let banks = parse(EXAMPLE_INPUT).expect("expected valid input");
+        let constraints = Constraints::default();
+
+        // when
+        let lowest = joltage_sum(&banks, 2, &constraints, Order::Smallest);
+        let top = format_top(&banks[..2], 2, 3, &constraints, Order::Largest);
+        let bottom = format_top(&banks[1..2], 3, 3, &constraints, Order::Smallest);
+
+        // then
+        // 11 + 11 + 22 + 11
+        assert_eq!(lowest.to_string(), "55");
+        assert_eq!(top, "bank 1: 98, 97, 96\nbank 2: 89, 81, 19\n");
+        assert_eq!(bottom, "bank 1: 111, 119, 811\n");
+    }
 }
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Order {
+    Largest,
+    Smallest,
+}
+
+impl Order {
+    // the i-th digit to try, best first
+    fn digit(self, i: usize) -> usize {
+        match self {
+            Order::Largest => 9 - i,
+            Order::Smallest => i,
+        }
+    }
+}
+
+// Chooses n batteries from a bank, leaving out dead cells and keeping at least `min_gap`
+// batteries between two chosen ones.
+//
+// The values are built digit by digit, best digit first. For a digit we only need to look at
+// its first allowed position: every choice after a later position is also possible after the
+// first one. The digit is only possible if enough batteries can still be chosen after it,
+// which `reach` tells us, so we never run into a dead end. That makes the best value a single
+// pass over the digits, and the next best values are found by backtracking.
+pub struct Selector<'a> {
+    bank: &'a [u64],
+    n: usize,
+    min_gap: usize,
+    // number of batteries we could choose from position i on
+    reach: Vec<usize>,
+    // next_digit[i][d] is the first allowed position with digit d from position i on, or the
+    // length of the bank if there is none
+    next_digit: Vec<[usize; 10]>,
+}
+
+impl<'a> Selector<'a> {
+    pub fn new(
+        bank: &'a [u64],
+        n: usize,
+        min_gap: usize,
+        forbidden: &HashSet<usize>,
+    ) -> Selector<'a> {
+        let len = bank.len();
+        let mut reach: Vec<usize> = vec![0; len + 1];
+        let mut next_digit: Vec<[usize; 10]> = vec![[len; 10]; len + 1];
+        for i in (0..len).rev() {
+            next_digit[i] = next_digit[i + 1];
+            reach[i] = reach[i + 1];
+            if !forbidden.contains(&i) {
+                next_digit[i][bank[i] as usize] = i;
+                reach[i] = reach[i].max(1 + reach_from(&reach, after(i, min_gap)));
+            }
+        }
+        Selector {
+            bank,
+            n,
+            min_gap,
+            reach,
+            next_digit,
+        }
+    }
+
+    // the positions of the best value, or no positions if there are not enough batteries
+    pub fn best(&self, order: Order) -> Vec<usize> {
+        self.top(1, order).pop().unwrap_or_default()
+    }
+
+    // The positions of the k best distinct values, best first. Of several ways to get the same
+    // value, this takes the earliest batteries.
+    pub fn top(&self, k: usize, order: Order) -> Vec<Vec<usize>> {
+        let mut results: Vec<Vec<usize>> = Vec::new();
+        if k == 0 || self.reach[0] < self.n {
+            return results;
+        }
+        if self.n == 0 {
+            return vec![vec![]];
+        }
+        let mut chosen: Vec<usize> = Vec::with_capacity(self.n);
+        // for every chosen battery and the next one: how many digits we already tried
+        let mut tried: Vec<usize> = vec![0];
+        loop {
+            if chosen.len() == self.n {
+                results.push(chosen.clone());
+                if results.len() == k {
+                    return results;
+                }
+                chosen.pop();
+                tried.pop();
+                continue;
+            }
+            let depth = chosen.len();
+            let start = chosen
+                .last()
+                .map_or(0, |p| after(*p, self.min_gap))
+                .min(self.bank.len());
+            let needed_after = self.n - depth - 1;
+            let next = (tried[depth]..10).find_map(|i| {
+                let p = self.next_digit[start][order.digit(i)];
+                (p < self.bank.len()
+                    && reach_from(&self.reach, after(p, self.min_gap)) >= needed_after)
+                    .then_some((i, p))
+            });
+            match next {
+                Some((i, p)) => {
+                    tried[depth] = i + 1;
+                    chosen.push(p);
+                    tried.push(0);
+                }
+                None if depth == 0 => return results,
+                None => {
+                    chosen.pop();
+                    tried.pop();
+                }
+            }
+        }
+    }
+}
+
+// the first position that may be chosen after position p, the gap may be huge
+fn after(p: usize, min_gap: usize) -> usize {
+    p.saturating_add(min_gap).saturating_add(1)
+}
+
+// there is nothing to choose behind the end of the bank
+fn reach_from(reach: &[usize], i: usize) -> usize {
+    reach.get(i).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rng::Rng;
+
+    // all choices of n batteries, in the order of their positions
+    fn all_choices(
+        bank: &[u64],
+        n: usize,
+        min_gap: usize,
+        forbidden: &HashSet<usize>,
+    ) -> Vec<Vec<usize>> {
+        fn choose(
+            len: usize,
+            n: usize,
+            min_gap: usize,
+            forbidden: &HashSet<usize>,
+            chosen: &mut Vec<usize>,
+            choices: &mut Vec<Vec<usize>>,
+        ) {
+            if chosen.len() == n {
+                choices.push(chosen.clone());
+                return;
+            }
+            let start = chosen.last().map_or(0, |p| p + min_gap + 1);
+            for i in (start..len).filter(|i| !forbidden.contains(i)) {
+                chosen.push(i);
+                choose(len, n, min_gap, forbidden, chosen, choices);
+                chosen.pop();
+            }
+        }
+        let mut choices = Vec::new();
+        choose(bank.len(), n, min_gap, forbidden, &mut vec![], &mut choices);
+        choices
+    }
+
+    // the first choice for every distinct value, best value first
+    fn top_by_brute_force(
+        bank: &[u64],
+        n: usize,
+        min_gap: usize,
+        forbidden: &HashSet<usize>,
+        k: usize,
+        order: Order,
+    ) -> Vec<Vec<usize>> {
+        let digits =
+            |positions: &[usize]| -> Vec<u64> { positions.iter().map(|p| bank[*p]).collect() };
+        let mut best: Vec<Vec<usize>> = Vec::new();
+        for choice in all_choices(bank, n, min_gap, forbidden) {
+            if !best.iter().any(|b| digits(b) == digits(&choice)) {
+                best.push(choice);
+            }
+        }
+        // stable sort, so the earliest choice stays first for equal values
+        best.sort_by(|a, b| match order {
+            Order::Largest => digits(b).cmp(&digits(a)),
+            Order::Smallest => digits(a).cmp(&digits(b)),
+        });
+        best.truncate(k);
+        best
+    }
+
+    #[test]
+    fn selector_handles_gaps_larger_than_bank() {
+        // given
+        let bank = [3, 9, 1, 4];
+
+        for min_gap in [4, 3_000_000_000, usize::MAX] {
+            // when
+            let selector = Selector::new(&bank, 1, min_gap, &HashSet::new());
+            let pair = Selector::new(&bank, 2, min_gap, &HashSet::new());
+
+            // then
+            assert_eq!(
+                selector.top(4, Order::Largest),
+                vec![vec![1], vec![3], vec![0], vec![2]]
+            );
+            assert_eq!(pair.best(Order::Largest), Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn selector_agrees_with_brute_force() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..3000 {
+            // given
+            let len = (rng.next() % 13) as usize;
+            let digit_count = rng.next() % 10 + 1;
+            let bank: Vec<u64> = (0..len).map(|_| rng.next() % digit_count).collect();
+            let n = (rng.next() % 6) as usize + 1;
+            let min_gap = (rng.next() % 3) as usize;
+            let forbidden: HashSet<usize> =
+                (0..len).filter(|_| rng.next().is_multiple_of(4)).collect();
+            let k = (rng.next() % 5) as usize + 1;
+            let selector = Selector::new(&bank, n, min_gap, &forbidden);
+
+            for order in [Order::Largest, Order::Smallest] {
+                // when
+                let top = selector.top(k, order);
+
+                // then
+                let expected = top_by_brute_force(&bank, n, min_gap, &forbidden, k, order);
+                assert_eq!(
+                    top, expected,
+                    "{order:?} differs for bank {bank:?}, n = {n}, gap {min_gap}, forbidden {forbidden:?}, k = {k}"
+                );
+                assert_eq!(
+                    selector.best(order),
+                    expected.first().cloned().unwrap_or_default()
+                );
+            }
+        }
+    }
+}
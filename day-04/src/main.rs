@@ -1,51 +1,254 @@
 #![forbid(unsafe_code)]
 
 use std::env;
+use std::fmt::Display;
 use std::fs::read_to_string;
 use std::path::Path;
+use std::str::FromStr;
+
+mod neighbourhood;
+#[cfg(test)]
+mod rng;
+mod sparse;
+
+use neighbourhood::{Access, Neighbourhood};
+use sparse::parse_coordinates;
 
 fn main() -> Result<(), String> {
-    let filename = env::args()
-        .nth(1)
-        .ok_or_else(|| "No file name given.".to_owned())?;
-    let content = read_to_string(Path::new(&filename)).map_err(|e| e.to_string())?;
-    let warehouse = parse(&content)?;
+    let options = parse_args(env::args().skip(1))?;
+    let content = read_to_string(Path::new(&options.filename)).map_err(|e| e.to_string())?;
+    let access = &options.access;
+
+    if options.coordinates {
+        let warehouse = parse_coordinates(&content)?;
+        if options.waves {
+            let rounds = removal_rounds(&warehouse, access);
+            print!("{}", format_roll_rounds(&warehouse, &rounds));
+            print_waves(&warehouse, &rounds);
+        } else {
+            print_removable_rolls(&warehouse, access);
+        }
+    } else {
+        let warehouse = parse(&content)?;
+        if options.waves {
+            let rounds = removal_rounds(&warehouse, access);
+            print!("{}", format_rounds(&warehouse, &rounds));
+            print_waves(&warehouse, &rounds);
+        } else {
+            print_removable_rolls(&warehouse, access);
+        }
+    }
 
-    let accessible_rolls = count_accessible_rolls(&warehouse);
+    Ok(())
+}
+
+fn print_removable_rolls(floor: &impl Floor, access: &Access) {
+    let accessible_rolls = count_accessible_rolls(floor, access);
     println!("{accessible_rolls} rolls of paper can be accessed by a forklift");
 
-    let original_rolls = warehouse.tiles.iter().filter(|tile| **tile).count();
-    let leftover_rolls = remove_rolls(warehouse);
+    let original_rolls = floor.rolls().count();
+    let leftover_rolls = remove_rolls(floor, access);
     println!(
         "{} rolls of paper can be removed",
         original_rolls - leftover_rolls
     );
+}
 
-    Ok(())
+fn print_waves(floor: &impl Floor, rounds: &[Option<usize>]) {
+    let sizes = wave_sizes(rounds);
+    for (round, size) in sizes.iter().enumerate() {
+        println!("round {}: {size} rolls", round + 1);
+    }
+    let staying = floor.rolls().count() - rounds.iter().flatten().count();
+    println!(
+        "{} rolls removed in {} rounds, {staying} rolls stay",
+        sizes.iter().sum::<usize>(),
+        sizes.len()
+    );
 }
 
-fn count_accessible_rolls(warehouse: &Warehouse) -> usize {
-    warehouse
-        .tiles
-        .iter()
-        .enumerate()
-        .filter(|(_, tile)| **tile)
-        .filter(|(i, _)| {
-            let x = i % warehouse.width;
-            let y = i / warehouse.width;
-            warehouse.count_neighbours(x, y) < 4
-        })
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Options {
+    filename: String,
+    // print the round in which every roll is removed and the size of every wave
+    waves: bool,
+    access: Access,
+    // the input lists the positions of the rolls instead of drawing the warehouse
+    coordinates: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut filename: Option<String> = None;
+    let mut waves = false;
+    let mut neighbourhood = Neighbourhood::Moore;
+    let mut threshold: usize = 4;
+    let mut coordinates = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--waves" => waves = true,
+            "--neighbourhood" => neighbourhood = parse_option_value(&arg, args.next())?,
+            "--threshold" => threshold = parse_option_value(&arg, args.next())?,
+            "--coordinates" => coordinates = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{arg}'")),
+            _ => {
+                if filename.is_some() {
+                    return Err(format!("unexpected extra argument '{arg}'"));
+                }
+                filename = Some(arg);
+            }
+        }
+    }
+
+    Ok(Options {
+        filename: filename.ok_or_else(|| "No file name given.".to_owned())?,
+        waves,
+        access: Access::new(&neighbourhood, threshold),
+        coordinates,
+    })
+}
+
+fn parse_option_value<T>(option: &str, value: Option<String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = value.ok_or_else(|| format!("option '{option}' needs a value"))?;
+    value
+        .parse()
+        .map_err(|e| format!("invalid value '{value}' for option '{option}': {e}"))
+}
+
+fn count_accessible_rolls(floor: &impl Floor, access: &Access) -> usize {
+    floor
+        .rolls()
+        .filter(|slot| floor.count_neighbours(*slot, &access.offsets) < access.threshold)
         .count()
 }
 
-fn remove_rolls(mut warehouse: Warehouse) -> usize {
+// Removes accessible rolls until there are none left and returns the number of rolls that
+// stay. Every roll keeps count of its neighbours. Removing a roll only changes the counts of
+// its neighbours, so only those can become accessible and need to be checked again.
+fn remove_rolls(floor: &impl Floor, access: &Access) -> usize {
+    let mut counts: Vec<usize> = vec![0; floor.slots()];
+    let mut present: Vec<bool> = vec![false; floor.slots()];
+    let mut queued: Vec<bool> = vec![false; floor.slots()];
+    let mut queue: Vec<usize> = Vec::new();
+    for i in floor.rolls() {
+        present[i] = true;
+        counts[i] = floor.count_neighbours(i, &access.offsets);
+        if counts[i] < access.threshold {
+            queued[i] = true;
+            queue.push(i);
+        }
+    }
+
+    while let Some(i) = queue.pop() {
+        present[i] = false;
+        for j in floor.neighbours(i, &access.reversed_offsets) {
+            if present[j] {
+                counts[j] -= 1;
+                if counts[j] < access.threshold && !queued[j] {
+                    queued[j] = true;
+                    queue.push(j);
+                }
+            }
+        }
+    }
+    present.iter().filter(|present| **present).count()
+}
+
+// For every slot, the round in which the roll in it is removed (counted from 1), where a
+// round removes all rolls that are accessible at once. Empty slots and rolls that are never
+// removed get `None`. The rolls of a round can only make their neighbours accessible for the
+// next round, so this goes through the rounds like a breadth first search.
+fn removal_rounds(floor: &impl Floor, access: &Access) -> Vec<Option<usize>> {
+    let mut rounds: Vec<Option<usize>> = vec![None; floor.slots()];
+    let mut counts: Vec<usize> = vec![0; floor.slots()];
+    let mut wave: Vec<usize> = Vec::new();
+    for i in floor.rolls() {
+        counts[i] = floor.count_neighbours(i, &access.offsets);
+        if counts[i] < access.threshold {
+            rounds[i] = Some(1);
+            wave.push(i);
+        }
+    }
+
+    let mut round = 1;
+    while !wave.is_empty() {
+        let mut next_wave: Vec<usize> = Vec::new();
+        for i in wave {
+            for j in floor.neighbours(i, &access.reversed_offsets) {
+                if rounds[j].is_none() {
+                    counts[j] -= 1;
+                    if counts[j] < access.threshold {
+                        rounds[j] = Some(round + 1);
+                        next_wave.push(j);
+                    }
+                }
+            }
+        }
+        wave = next_wave;
+        round += 1;
+    }
+    rounds
+}
+
+// number of rolls removed in every round
+fn wave_sizes(rounds: &[Option<usize>]) -> Vec<usize> {
+    let mut sizes: Vec<usize> = vec![0; rounds.iter().flatten().max().copied().unwrap_or(0)];
+    for round in rounds.iter().flatten() {
+        sizes[round - 1] += 1;
+    }
+    sizes
+}
+
+// Writes the warehouse with the removal round instead of every removed roll. Rolls that stay
+// are still '@'. All tiles get the same width so the columns line up.
+fn format_rounds(warehouse: &Warehouse, rounds: &[Option<usize>]) -> String {
+    let width = rounds
+        .iter()
+        .flatten()
+        .max()
+        .map_or(1, |max| max.to_string().len());
+    let mut out = String::new();
+    for row in 0..warehouse.height {
+        let tiles: Vec<String> = (row * warehouse.width..(row + 1) * warehouse.width)
+            .map(|i| match (warehouse.tiles[i], rounds[i]) {
+                (_, Some(round)) => format!("{round:>width$}"),
+                (true, None) => format!("{:>width$}", '@'),
+                (false, None) => format!("{:>width$}", '.'),
+            })
+            .collect();
+        out.push_str(&tiles.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+// writes the position of every roll with its removal round, or '@' if it stays
+fn format_roll_rounds(floor: &impl Floor, rounds: &[Option<usize>]) -> String {
+    let mut out = String::new();
+    for slot in floor.rolls() {
+        let (x, y) = floor.position(slot);
+        match rounds[slot] {
+            Some(round) => out.push_str(&format!("{x},{y}: {round}\n")),
+            None => out.push_str(&format!("{x},{y}: @\n")),
+        }
+    }
+    out
+}
+
+// the original solution, scans the whole warehouse until nothing changes
+#[cfg(test)]
+fn remove_rolls_by_rescan(mut warehouse: Warehouse, access: &Access) -> usize {
     let mut changed = true;
     while changed {
         changed = false;
         for i in 0..warehouse.tiles.len() {
-            let x = i % warehouse.width;
-            let y = i / warehouse.width;
-            if warehouse.tiles[i] && warehouse.count_neighbours(x, y) < 4 {
+            if warehouse.tiles[i]
+                && warehouse.count_neighbours(i, &access.offsets) < access.threshold
+            {
                 warehouse.tiles[i] = false;
                 changed = true;
             }
@@ -54,6 +257,28 @@ fn remove_rolls(mut warehouse: Warehouse) -> usize {
     warehouse.tiles.iter().filter(|tile| **tile).count()
 }
 
+// A warehouse floor with rolls on it. Every roll has a slot, a number below `slots()`, so
+// the algorithms can keep what they know about the rolls in vectors, no matter how the
+// floor stores them.
+trait Floor {
+    fn slots(&self) -> usize;
+    // the slot of the roll at the position, `None` if there is no roll
+    fn slot(&self, position: (i64, i64)) -> Option<usize>;
+    fn position(&self, slot: usize) -> (i64, i64);
+    fn rolls(&self) -> impl Iterator<Item = usize>;
+
+    // slots of the rolls at the given offsets from the roll in the given slot
+    fn neighbours(&self, slot: usize, offsets: &[(i64, i64)]) -> impl Iterator<Item = usize> {
+        let (x, y) = self.position(slot);
+        offsets
+            .iter()
+            .filter_map(move |(dx, dy)| self.slot((x.checked_add(*dx)?, y.checked_add(*dy)?)))
+    }
+    fn count_neighbours(&self, slot: usize, offsets: &[(i64, i64)]) -> usize {
+        self.neighbours(slot, offsets).count()
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 struct Warehouse {
     tiles: Vec<bool>,
@@ -61,31 +286,29 @@ struct Warehouse {
     height: usize,
 }
 
-impl Warehouse {
-    fn get(&self, x: usize, y: usize) -> Option<bool> {
-        if x < self.width && y < self.height {
-            self.tiles.get(x + y * self.width).copied()
-        } else {
-            None
-        }
+// every tile is a slot, the empty ones are just never used
+impl Floor for Warehouse {
+    fn slots(&self) -> usize {
+        self.tiles.len()
     }
-    fn count_neighbours(&self, x: usize, y: usize) -> usize {
-        let mut count: usize = 0;
-        if x > 0 && y > 0 {
-            count += self.get(x - 1, y - 1).unwrap_or(false) as usize;
-        }
-        if x > 0 {
-            count += self.get(x - 1, y).unwrap_or(false) as usize;
-            count += self.get(x - 1, y + 1).unwrap_or(false) as usize;
-        }
-        if y > 0 {
-            count += self.get(x, y - 1).unwrap_or(false) as usize;
-            count += self.get(x + 1, y - 1).unwrap_or(false) as usize;
-        }
-        count += self.get(x + 1, y).unwrap_or(false) as usize;
-        count += self.get(x, y + 1).unwrap_or(false) as usize;
-        count += self.get(x + 1, y + 1).unwrap_or(false) as usize;
-        count
+
+    fn slot(&self, (x, y): (i64, i64)) -> Option<usize> {
+        let x = usize::try_from(x).ok().filter(|x| *x < self.width)?;
+        let y = usize::try_from(y).ok().filter(|y| *y < self.height)?;
+        let i = x + y * self.width;
+        self.tiles[i].then_some(i)
+    }
+
+    fn position(&self, slot: usize) -> (i64, i64) {
+        ((slot % self.width) as i64, (slot / self.width) as i64)
+    }
+
+    fn rolls(&self) -> impl Iterator<Item = usize> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile)
+            .map(|(i, _)| i)
     }
 }
 
@@ -118,6 +341,8 @@ fn parse(input: &str) -> Result<Warehouse, String> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rng::Rng;
+    use std::time::Instant;
 
     static EXAMPLE_INPUT: &str = r#"..@@.@@@@.
 @@@.@.@.@@
@@ -137,9 +362,212 @@ mod test {
         let warehouse = parse(EXAMPLE_INPUT).expect("expected valid input");
 
         // when
-        let count = count_accessible_rolls(&warehouse);
+        let count = count_accessible_rolls(&warehouse, &Access::default());
 
         // then
         assert_eq!(count, 13);
     }
+
+    fn random_warehouse(rng: &mut Rng, width: usize, height: usize) -> Warehouse {
+        // mostly rolls, so it takes a while to peel them off
+        let tiles = (0..width * height).map(|_| rng.next() % 10 < 7).collect();
+        Warehouse {
+            tiles,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn remove_rolls_works_for_example() {
+        // given
+        let warehouse = parse(EXAMPLE_INPUT).expect("expected valid input");
+        let original_rolls = warehouse.tiles.iter().filter(|tile| **tile).count();
+
+        // when
+        let leftover_rolls = remove_rolls(&warehouse, &Access::default());
+
+        // then
+        assert_eq!(original_rolls - leftover_rolls, 43);
+    }
+
+    #[test]
+    fn remove_rolls_agrees_with_rescan() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..300 {
+            // given
+            let width = (rng.next() % 20) as usize + 1;
+            let height = (rng.next() % 20) as usize + 1;
+            let warehouse = random_warehouse(&mut rng, width, height);
+
+            // when
+            let leftover_rolls = remove_rolls(&warehouse, &Access::default());
+
+            // then
+            assert_eq!(
+                leftover_rolls,
+                remove_rolls_by_rescan(warehouse, &Access::default())
+            );
+        }
+    }
+
+    // cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_remove_rolls() {
+        let warehouse = random_warehouse(&mut Rng(0x9e3779b97f4a7c15), 5000, 5000);
+
+        let start = Instant::now();
+        let leftover_rolls = remove_rolls(&warehouse, &Access::default());
+        println!("worklist: {:?}", start.elapsed());
+
+        let start = Instant::now();
+        let leftover_rolls_by_rescan = remove_rolls_by_rescan(warehouse, &Access::default());
+        println!("rescan: {:?}", start.elapsed());
+
+        assert_eq!(leftover_rolls, leftover_rolls_by_rescan);
+    }
+
+    // removes all accessible rolls at once, one round after another
+    fn removal_rounds_by_simulation(
+        mut warehouse: Warehouse,
+        access: &Access,
+    ) -> Vec<Option<usize>> {
+        let mut rounds: Vec<Option<usize>> = vec![None; warehouse.tiles.len()];
+        for round in 1.. {
+            let accessible: Vec<usize> = (0..warehouse.tiles.len())
+                .filter(|i| warehouse.tiles[*i])
+                .filter(|i| warehouse.count_neighbours(*i, &access.offsets) < access.threshold)
+                .collect();
+            if accessible.is_empty() {
+                break;
+            }
+            for i in accessible {
+                warehouse.tiles[i] = false;
+                rounds[i] = Some(round);
+            }
+        }
+        rounds
+    }
+
+    #[test]
+    fn removal_rounds_work_for_example() {
+        // given
+        let warehouse = parse(EXAMPLE_INPUT).expect("expected valid input");
+
+        // when
+        let rounds = removal_rounds(&warehouse, &Access::default());
+
+        // then
+        assert_eq!(wave_sizes(&rounds), vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+        assert_eq!(
+            format_rounds(&warehouse, &rounds).lines().next(),
+            Some(". . 1 1 . 1 1 2 1 .")
+        );
+    }
+
+    #[test]
+    fn removal_rounds_agree_with_simulation() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let neighbourhoods = [
+            Neighbourhood::Moore,
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Radius(2),
+            // not symmetric, the counts of other rolls change than the ones we look at
+            Neighbourhood::Custom(vec![(1, 0), (2, 1), (0, -1)]),
+        ];
+        for _ in 0..300 {
+            // given
+            let width = (rng.next() % 20) as usize + 1;
+            let height = (rng.next() % 20) as usize + 1;
+            let warehouse = random_warehouse(&mut rng, width, height);
+            let neighbourhood = &neighbourhoods[(rng.next() % 4) as usize];
+            let threshold = (rng.next() % 6) as usize;
+            let access = Access::new(neighbourhood, threshold);
+
+            // when
+            let rounds = removal_rounds(&warehouse, &access);
+
+            // then
+            assert_eq!(
+                rounds,
+                removal_rounds_by_simulation(warehouse.clone(), &access),
+                "rounds differ for {neighbourhood:?} and threshold {threshold}"
+            );
+            assert_eq!(
+                rounds.iter().filter(|round| round.is_none()).count()
+                    - warehouse.tiles.iter().filter(|tile| !**tile).count(),
+                remove_rolls(&warehouse, &access)
+            );
+            assert_eq!(
+                remove_rolls(&warehouse, &access),
+                remove_rolls_by_rescan(warehouse, &access)
+            );
+        }
+    }
+
+    #[test]
+    fn von_neumann_neighbourhood_ignores_diagonals() {
+        // given
+        let warehouse = parse(EXAMPLE_INPUT).expect("expected valid input");
+        let access = Access::new(&Neighbourhood::VonNeumann, 2);
+
+        // when
+        let count = count_accessible_rolls(&warehouse, &access);
+
+        // then
+        let by_hand = (0..warehouse.tiles.len())
+            .filter(|i| warehouse.tiles[*i])
+            .filter(|i| {
+                let (x, y) = (i % warehouse.width, i / warehouse.width);
+                let roll_at = |x: Option<usize>, y: Option<usize>| match (x, y) {
+                    (Some(x), Some(y)) if x < warehouse.width && y < warehouse.height => {
+                        warehouse.tiles[x + y * warehouse.width] as usize
+                    }
+                    _ => 0,
+                };
+                roll_at(x.checked_sub(1), Some(y))
+                    + roll_at(Some(x + 1), Some(y))
+                    + roll_at(Some(x), y.checked_sub(1))
+                    + roll_at(Some(x), Some(y + 1))
+                    < 2
+            })
+            .count();
+        assert_eq!(count, by_hand);
+    }
+
+    #[test]
+    fn sparse_warehouse_agrees_with_grid() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..100 {
+            // given
+            let width = (rng.next() % 20) as usize + 1;
+            let height = (rng.next() % 20) as usize + 1;
+            let warehouse = random_warehouse(&mut rng, width, height);
+            // far away from the origin, on a grid this would never fit into memory
+            let (shift_x, shift_y) = (-1_000_000_000_000i64, 4_000_000_000_000i64);
+            let input: String = warehouse
+                .rolls()
+                .map(|i| {
+                    let (x, y) = warehouse.position(i);
+                    format!("{},{}\n", x + shift_x, y + shift_y)
+                })
+                .collect();
+            let sparse = parse_coordinates(&input).expect("expected valid input");
+            let access = Access::new(&Neighbourhood::Radius(2), 9);
+
+            // when
+            let count = count_accessible_rolls(&sparse, &access);
+            let leftover_rolls = remove_rolls(&sparse, &access);
+            let rounds = removal_rounds(&sparse, &access);
+
+            // then
+            assert_eq!(count, count_accessible_rolls(&warehouse, &access));
+            assert_eq!(leftover_rolls, remove_rolls(&warehouse, &access));
+            let grid_rounds = removal_rounds(&warehouse, &access);
+            for (slot, i) in warehouse.rolls().enumerate() {
+                assert_eq!(rounds[slot], grid_rounds[i]);
+            }
+        }
+    }
 }
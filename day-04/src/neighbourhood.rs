@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+// The tiles around a roll that count as its neighbours, given as offsets (dx, dy).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Neighbourhood {
+    // the eight surrounding tiles
+    Moore,
+    // only the four tiles left, right, above and below
+    VonNeumann,
+    // all tiles at most r steps away in both directions, radius 1 is the same as Moore
+    Radius(usize),
+    Custom(Vec<(i64, i64)>),
+}
+
+impl Neighbourhood {
+    pub fn offsets(&self) -> Vec<(i64, i64)> {
+        match self {
+            Neighbourhood::Moore => Neighbourhood::Radius(1).offsets(),
+            Neighbourhood::VonNeumann => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighbourhood::Radius(r) => {
+                let r = *r as i64;
+                (-r..=r)
+                    .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+                    .filter(|offset| *offset != (0, 0))
+                    .collect()
+            }
+            Neighbourhood::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+// Every roll looks at (2r + 1)² - 1 tiles, so large radii make counting neighbours take
+// practically forever.
+const MAX_RADIUS: usize = 50;
+
+impl FromStr for Neighbourhood {
+    type Err = String;
+
+    // "moore", "von-neumann", "radius:R" or "custom:DX,DY;DX,DY;..."
+    fn from_str(s: &str) -> Result<Neighbourhood, String> {
+        match s.split_once(':') {
+            None if s == "moore" => Ok(Neighbourhood::Moore),
+            None if s == "von-neumann" => Ok(Neighbourhood::VonNeumann),
+            Some(("radius", r)) => match r.parse() {
+                Ok(0) => Err("the radius must be at least 1".to_owned()),
+                Ok(r) if r > MAX_RADIUS => Err(format!("the radius must be at most {MAX_RADIUS}")),
+                Ok(r) => Ok(Neighbourhood::Radius(r)),
+                Err(e) => Err(format!("invalid radius '{r}': {e}")),
+            },
+            Some(("custom", offsets)) => {
+                let mut parsed: Vec<(i64, i64)> = offsets
+                    .split(';')
+                    .map(parse_offset)
+                    .collect::<Result<_, _>>()?;
+                parsed.sort_unstable();
+                parsed.dedup();
+                Ok(Neighbourhood::Custom(parsed))
+            }
+            _ => Err(format!(
+                "unknown neighbourhood '{s}', expected 'moore', 'von-neumann', 'radius:R' or 'custom:DX,DY;DX,DY;...'"
+            )),
+        }
+    }
+}
+
+fn parse_offset(s: &str) -> Result<(i64, i64), String> {
+    let (dx, dy) = s
+        .split_once(',')
+        .ok_or_else(|| format!("invalid offset '{s}', expected 'DX,DY'"))?;
+    let offset = (
+        dx.trim()
+            .parse()
+            .map_err(|e| format!("invalid offset '{s}': {e}"))?,
+        dy.trim()
+            .parse()
+            .map_err(|e| format!("invalid offset '{s}': {e}"))?,
+    );
+    if offset == (0, 0) {
+        return Err("a roll cannot be its own neighbour".to_owned());
+    }
+    // we also need to look in the opposite direction
+    if offset.0 == i64::MIN || offset.1 == i64::MIN {
+        return Err(format!("offset '{s}' cannot be reversed"));
+    }
+    Ok(offset)
+}
+
+// A roll is accessible if fewer than `threshold` rolls are at the offsets around it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Access {
+    pub offsets: Vec<(i64, i64)>,
+    // A removed roll lowers the count of every roll that has it at one of its offsets. For
+    // neighbourhoods that are not symmetric, these are not the rolls at its own offsets.
+    pub reversed_offsets: Vec<(i64, i64)>,
+    pub threshold: usize,
+}
+
+impl Access {
+    pub fn new(neighbourhood: &Neighbourhood, threshold: usize) -> Access {
+        let offsets = neighbourhood.offsets();
+        let reversed_offsets = offsets.iter().map(|(dx, dy)| (-dx, -dy)).collect();
+        Access {
+            offsets,
+            reversed_offsets,
+            threshold,
+        }
+    }
+}
+
+// the rules from the puzzle
+impl Default for Access {
+    fn default() -> Access {
+        Access::new(&Neighbourhood::Moore, 4)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn neighbourhoods_parse_and_have_the_right_offsets() {
+        // given
+        let inputs = ["moore", "von-neumann", "radius:2", "custom:1,0; 0,2;1,0"];
+
+        // when
+        let neighbourhoods: Vec<Neighbourhood> = inputs
+            .iter()
+            .map(|s| s.parse().expect("expected valid neighbourhood"))
+            .collect();
+
+        // then
+        assert_eq!(neighbourhoods[0].offsets().len(), 8);
+        assert_eq!(neighbourhoods[1].offsets().len(), 4);
+        assert_eq!(neighbourhoods[2].offsets().len(), 24);
+        assert_eq!(
+            neighbourhoods[3],
+            Neighbourhood::Custom(vec![(0, 2), (1, 0)])
+        );
+        assert!("custom:0,0".parse::<Neighbourhood>().is_err());
+        assert!("radius:0".parse::<Neighbourhood>().is_err());
+        assert!("radius:1000000".parse::<Neighbourhood>().is_err());
+        assert!(
+            "custom:-9223372036854775808,0"
+                .parse::<Neighbourhood>()
+                .is_err()
+        );
+        assert!(
+            "custom:9223372036854775807,-9223372036854775807"
+                .parse::<Neighbourhood>()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use super::Floor;
+use std::collections::HashMap;
+
+// Only knows where the rolls are, for huge warehouses that are mostly empty. The slots are
+// the order in which the rolls were given.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SparseWarehouse {
+    slots: HashMap<(i64, i64), usize>,
+    positions: Vec<(i64, i64)>,
+}
+
+impl Floor for SparseWarehouse {
+    fn slots(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn slot(&self, position: (i64, i64)) -> Option<usize> {
+        self.slots.get(&position).copied()
+    }
+
+    fn position(&self, slot: usize) -> (i64, i64) {
+        self.positions[slot]
+    }
+
+    fn rolls(&self) -> impl Iterator<Item = usize> {
+        0..self.positions.len()
+    }
+}
+
+// Reads one roll per line as "x,y", e.g.
+//
+// # comments and blank lines are ignored
+// 0,0
+// -3,1000000000000
+pub fn parse_coordinates(input: &str) -> Result<SparseWarehouse, String> {
+    let mut warehouse = SparseWarehouse::default();
+    for (line_no, line) in input.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let position = parse_position(line).map_err(|e| format!("line {}: {e}", line_no + 1))?;
+        if warehouse.slots.contains_key(&position) {
+            return Err(format!(
+                "line {}: there already is a roll at {line}",
+                line_no + 1
+            ));
+        }
+        warehouse.slots.insert(position, warehouse.positions.len());
+        warehouse.positions.push(position);
+    }
+    Ok(warehouse)
+}
+
+fn parse_position(s: &str) -> Result<(i64, i64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("invalid position '{s}', expected 'x,y'"))?;
+    Ok((
+        x.trim()
+            .parse()
+            .map_err(|e| format!("invalid position '{s}': {e}"))?,
+        y.trim()
+            .parse()
+            .map_err(|e| format!("invalid position '{s}': {e}"))?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_coordinates_reads_far_apart_rolls() {
+        // given
+        let input = "# two groups\n0,0\n1,0\n\n-5000000000000, 7\n";
+
+        // when
+        let warehouse = parse_coordinates(input).expect("expected valid input");
+
+        // then
+        assert_eq!(warehouse.slots(), 3);
+        assert_eq!(warehouse.slot((1, 0)), Some(1));
+        assert_eq!(warehouse.slot((-5_000_000_000_000, 7)), Some(2));
+        assert_eq!(warehouse.slot((2, 0)), None);
+        assert_eq!(
+            warehouse
+                .neighbours(0, &[(1, 0), (-1, 0)])
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(
+            parse_coordinates("1,2\n1,2"),
+            Err("line 2: there already is a roll at 1,2".to_owned())
+        );
+    }
+}